@@ -0,0 +1,209 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::{
+    kart::{Kart, KartInput, Speed, CHASSIS_HALF_EXTENTS},
+    race::{checkpoint::LapProgress, state::RaceState, wrong_way::WrongWay},
+    track::racing_line::RacingLine,
+};
+
+/// Number of AI karts in a [`crate::race::RaceMode::Race`]
+pub const OPPONENT_COUNT: usize = 3;
+
+/// Seconds an AI kart can stay stopped before it respawns
+const STUCK_SECONDS: f32 = 2f32;
+/// Speed under which an AI kart is considered stopped, in meters per second
+const STUCK_SPEED: f32 = 0.5f32;
+/// Distance in meters under which an AI kart steers around the kart ahead
+const AVOID_DISTANCE: f32 = 4f32;
+/// Space kept between the sides of two karts when avoiding, in meters
+const AVOID_MARGIN: f32 = 0.5f32;
+/// Sideways distance between the centers of two karts under which they would
+/// collide, in meters
+const AVOID_WIDTH: f32 = CHASSIS_HALF_EXTENTS.x * 2f32 + AVOID_MARGIN;
+/// Speed over the target speed tolerated before braking, in meters per second
+const BRAKE_MARGIN: f32 = 0.5f32;
+
+/// How well the AI karts drive, chosen with `--difficulty <easy|normal|hard>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Reads the difficulty from the `--difficulty <level>` command line
+    /// argument, [`Difficulty::Normal`] by default
+    pub fn from_args() -> Self {
+        let mut args = std::env::args()
+            .skip_while(|arg| arg != "--difficulty")
+            .skip(1);
+
+        match args.next().as_deref() {
+            Some("easy") => Self::Easy,
+            Some("hard") => Self::Hard,
+            Some("normal") | None => Self::Normal,
+            Some(other) => {
+                tracing::warn!("Unknown difficulty {other:?}, using normal");
+                Self::Normal
+            }
+        }
+    }
+
+    /// Fraction of the kart's top speed the AI drives at
+    fn speed_factor(&self) -> f32 {
+        match self {
+            Self::Easy => 0.75f32,
+            Self::Normal => 0.9f32,
+            Self::Hard => 1f32,
+        }
+    }
+
+    /// How much the AI slows down for a 90° corner, from 0 (not at all) to 1
+    /// (to a stop)
+    fn corner_caution(&self) -> f32 {
+        match self {
+            Self::Easy => 0.6f32,
+            Self::Normal => 0.45f32,
+            Self::Hard => 0.3f32,
+        }
+    }
+
+    /// Seconds of driving ahead the AI steers towards, shorter means tighter
+    /// lines
+    fn lookahead_seconds(&self) -> f32 {
+        match self {
+            Self::Easy => 0.8f32,
+            Self::Normal => 0.6f32,
+            Self::Hard => 0.5f32,
+        }
+    }
+}
+
+/// Drives the kart along the [`RacingLine`] by filling its [`KartInput`]
+#[derive(Debug, Component)]
+pub struct AiDriver {
+    pub difficulty: Difficulty,
+    /// Seconds the kart has been stopped
+    stuck_time: f32,
+}
+
+impl AiDriver {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Self {
+            difficulty,
+            stuck_time: 0f32,
+        }
+    }
+}
+
+/// Fills the [`KartInput`] of the AI karts, like a player would
+///
+/// The kart steers towards a point ahead on the racing line, brakes before
+/// the corners, steers around the karts in front of it, backs up to turn
+/// around when it goes the wrong way and respawns when it is stuck
+#[allow(clippy::type_complexity)]
+pub fn drive_ai(
+    fixed_time: Res<FixedTime>,
+    race_state: Res<State<RaceState>>,
+    racing_line: Res<RacingLine>,
+    mut karts: Query<(
+        Entity,
+        &Transform,
+        &Speed,
+        &Kart,
+        &LapProgress,
+        &WrongWay,
+        &mut KartInput,
+        &mut AiDriver,
+    )>,
+    // Ghosts have no lap progress, they can be driven through
+    others: Query<(Entity, &Transform), With<LapProgress>>,
+) {
+    let is_racing = matches!(race_state.get(), RaceState::Racing | RaceState::Finished);
+
+    for (entity, transform, speed, kart, progress, wrong_way, mut input, mut driver) in
+        karts.iter_mut()
+    {
+        *input = KartInput::default();
+        if !is_racing || progress.finished || !racing_line.is_valid() {
+            continue;
+        }
+
+        let difficulty = driver.difficulty;
+        let position = transform.translation;
+        let forward_speed = speed.forward_speed;
+        let distance = racing_line.closest(position).distance;
+
+        // Steer towards a point ahead, further the faster the kart goes
+        let lookahead =
+            (forward_speed.abs() * difficulty.lookahead_seconds()).max(kart.wheel_distance);
+        let target = racing_line.sample(distance + lookahead).point;
+        let local_target = transform.rotation.inverse() * (target - position);
+        // Left is +X, front is +Z
+        let target_angle = local_target.x.atan2(local_target.z);
+        let mut steering = target_angle / kart.max_steering_angle;
+
+        // Steer around the closest kart ahead
+        let blocking = others
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, other_transform)| {
+                transform.rotation.inverse() * (other_transform.translation - position)
+            })
+            .filter(|local| {
+                local.z > 0f32 && local.z < AVOID_DISTANCE && local.x.abs() < AVOID_WIDTH
+            })
+            .min_by(|a, b| a.z.total_cmp(&b.z));
+        if let Some(local) = blocking {
+            // Away from the other kart, harder the closer it is
+            let side = if local.x >= 0f32 { -1f32 } else { 1f32 };
+            steering += side * (1f32 - local.z / AVOID_DISTANCE);
+        }
+
+        // Slow down for the turn coming within the braking distance
+        let braking_distance =
+            forward_speed * forward_speed / (2f32 * kart.braking_deceleration) + lookahead;
+        let turn = racing_line
+            .sample(distance)
+            .tangent
+            .angle_between(racing_line.sample(distance + braking_distance).tangent);
+        let corner_factor = 1f32 - difficulty.corner_caution() * (turn / (PI / 2f32)).min(1f32);
+        let target_speed = kart.max_speed * difficulty.speed_factor() * corner_factor;
+
+        let mut throttle = if forward_speed > target_speed + BRAKE_MARGIN {
+            -1f32
+        } else if forward_speed < target_speed {
+            1f32
+        } else {
+            0f32
+        };
+
+        // Back up with the wheels turned away from the racing line, which
+        // swings the front towards it, until facing the right way again
+        if wrong_way.is_wrong_way {
+            throttle = -1f32;
+            steering = -target_angle.signum();
+        }
+
+        if forward_speed.abs() < STUCK_SPEED {
+            driver.stuck_time += fixed_time.period.as_secs_f32();
+        } else {
+            driver.stuck_time = 0f32;
+        }
+        let is_stuck = driver.stuck_time > STUCK_SECONDS;
+        if is_stuck {
+            driver.stuck_time = 0f32;
+        }
+
+        *input = KartInput {
+            throttle,
+            steering: steering.clamp(-1f32, 1f32),
+            respawn: is_stuck,
+            ..default()
+        };
+    }
+}
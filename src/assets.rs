@@ -1,31 +1,125 @@
-use bevy::prelude::*;
-use bevy_asset_loader::asset_collection::AssetCollection;
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use bevy_asset_loader::{
+    asset_collection::AssetCollection,
+    prelude::{DynamicAsset, DynamicAssetType},
+};
+
+use crate::{kart::registry::KartManifest, track::registry::TrackManifest};
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 pub enum AssetLoadingState {
     #[default]
+    Manifests,
     AssetLoading,
     Done,
+    Failed,
+}
+
+/// Manifests listing the game's content, loaded before everything else
+#[derive(Resource, AssetCollection)]
+pub struct ManifestAssets {
+    #[asset(path = "karts/manifest.karts.ron")]
+    pub karts: Handle<KartManifest>,
+    #[asset(path = "terrains/manifest.tracks.ron")]
+    pub tracks: Handle<TrackManifest>,
 }
 
 #[derive(Resource, AssetCollection)]
 pub struct KartAssets {
-    #[asset(path = "karts/sedan.glb#Scene0")]
-    pub sedan: Handle<Scene>,
-    #[asset(path = "karts/sports.glb#Scene0")]
-    pub sports: Handle<Scene>,
-    #[asset(path = "karts/muscle.glb#Scene0")]
-    pub muscle: Handle<Scene>,
-    #[asset(path = "karts/monster_truck.glb#Scene0")]
-    pub monster_truck: Handle<Scene>,
-    #[asset(path = "karts/police.glb#Scene0")]
-    pub police: Handle<Scene>,
+    /// Scenes and stats of every kart, registered from the kart manifest
+    ///
+    /// Use [`crate::kart::registry::KartRegistry`] to get them by kart, this
+    /// collection only makes the loading state wait for them
+    #[allow(unused)]
+    #[asset(key = "karts", collection)]
+    pub karts: Vec<HandleUntyped>,
 }
 
 #[derive(Resource, AssetCollection)]
-pub struct TerrainAssets {
-    #[asset(path = "terrains/map01.glb#Scene0")]
-    pub map01: Handle<Scene>,
-    #[asset(path = "terrains/map01.glb#Mesh0/Primitive0")]
-    pub map01_mesh: Handle<Mesh>,
+pub struct TrackAssets {
+    /// Scene, music and skybox of the selected track, registered from the
+    /// track manifest
+    ///
+    /// Use [`crate::track::registry::Track`] to get them, this collection only
+    /// makes the loading state wait for them
+    #[allow(unused)]
+    #[asset(key = "track", collection)]
+    pub track: Vec<HandleUntyped>,
+}
+
+/// An asset read from a RON file by a [`RonLoader`]
+pub trait RonAsset: serde::de::DeserializeOwned + TypeUuid + TypePath + Send + Sync {
+    type Error: std::fmt::Display;
+
+    /// What the asset is, for the errors, e.g. `"kart manifest"`
+    const NAME: &'static str;
+    /// Extensions of the asset's files, e.g. `karts.ron`
+    const EXTENSIONS: &'static [&'static str];
+
+    /// Checks what the RON format can't, e.g. that a value is positive
+    fn validate(&self) -> Result<(), Self::Error>;
+}
+
+/// Loads a [`RonAsset`], the asset fails to load when it is invalid
+pub struct RonLoader<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for RonLoader<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: RonAsset> AssetLoader for RonLoader<T> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let asset = ron::de::from_bytes::<T>(bytes)
+                .map_err(|err| err.to_string())
+                .and_then(|asset| {
+                    asset
+                        .validate()
+                        .map(|_| asset)
+                        .map_err(|err| err.to_string())
+                })
+                .map_err(|err| {
+                    bevy::asset::Error::msg(format!(
+                        "Invalid {} {:?}: {err}",
+                        T::NAME,
+                        load_context.path()
+                    ))
+                })?;
+
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
+
+/// Dynamic asset giving handles registered at runtime to a collection, e.g.
+/// [`KartAssets`], so the loading state waits for them
+#[derive(Debug)]
+pub struct DynamicHandles(pub Vec<HandleUntyped>);
+
+impl DynamicAsset for DynamicHandles {
+    fn load(&self, _asset_server: &AssetServer) -> Vec<HandleUntyped> {
+        self.0.clone()
+    }
+
+    fn build(&self, _world: &mut World) -> Result<DynamicAssetType, bevy::asset::Error> {
+        Ok(DynamicAssetType::Collection(self.0.clone()))
+    }
 }
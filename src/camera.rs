@@ -25,6 +25,8 @@ pub struct MainCamera {
 }
 
 const CAMERA_ARM: Vec3 = Vec3::new(0f32, 3f32, -6.5f32);
+/// How far down the camera is pushed per meter per second of landing impact
+const LANDING_DIP: f32 = 0.05f32;
 
 impl Default for MainCamera {
     fn default() -> Self {
@@ -90,10 +92,11 @@ pub fn sync_camera_to_player(
     #[cfg(feature = "cheat_input_target")] input_target: Res<crate::input::InputTarget>,
     #[cfg(feature = "cheat_input_target")] action_state: Res<ActionState<Action>>,
     time: Res<Time>,
-    player: Query<(&Transform, &kart::Speed), With<kart::Kart>>,
-    mut camera: Query<(&mut Transform, &MainCamera), Without<kart::Kart>>,
+    mut landed_events: EventReader<kart::jump::KartLanded>,
+    player: Query<(Entity, &Transform, &kart::Speed), With<kart::Player>>,
+    mut camera: Query<(&mut Transform, &MainCamera), Without<kart::Player>>,
 ) {
-    let (player_transform, _player_speed) = player.single();
+    let (player_entity, player_transform, _player_speed) = player.single();
     let (mut camera_transform, camera) = camera.single_mut();
     let camera_transform = camera_transform.as_mut();
 
@@ -119,6 +122,13 @@ pub fn sync_camera_to_player(
         .translation
         .lerp(target, interpolation_time);
 
+    // Hard landings shake the camera, it then catches up with the arm
+    for landed in landed_events.iter() {
+        if landed.kart == player_entity {
+            camera_transform.translation -= Vec3::Y * landed.impact * LANDING_DIP;
+        }
+    }
+
     // Update the camera rotation and add some rotation based on the kart's speed
 
     camera_transform.look_at(player_transform.translation + camera.look_at, Vec3::Y);
@@ -3,6 +3,7 @@
     feature = "debug_screen_fps",
     feature = "debug_screen_position",
     feature = "debug_screen_camera",
+    feature = "debug_screen_colliders",
 ))]
 pub mod screen;
 
@@ -11,6 +12,7 @@ pub mod screen;
     feature = "debug_screen_fps",
     feature = "debug_screen_position",
     feature = "debug_screen_camera",
+    feature = "debug_screen_colliders",
 )))]
 pub mod screen {
     use bevy::app::{App, Plugin};
@@ -24,3 +26,6 @@ pub mod screen {
 
 #[cfg(feature = "debug_input")]
 pub mod input;
+
+#[cfg(feature = "debug_racing_line")]
+pub mod racing_line;
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+use crate::track::racing_line::RacingLine;
+
+/// Height of the drawn line above the track, so it is not hidden by the road
+const DRAW_HEIGHT: f32 = 0.1f32;
+
+/// Draws the track's racing line in yellow and the edges of the track in red
+pub fn draw_racing_line(mut gizmos: Gizmos, racing_line: Res<RacingLine>) {
+    if !racing_line.is_valid() {
+        return;
+    }
+
+    let lift = Vec3::Y * DRAW_HEIGHT;
+    let samples: Vec<_> = racing_line.samples().collect();
+    let first = samples[0];
+
+    gizmos.linestrip(
+        samples
+            .iter()
+            .chain([&first])
+            .map(|sample| sample.point + lift),
+        Color::YELLOW,
+    );
+
+    for side in [-1f32, 1f32] {
+        gizmos.linestrip(
+            samples.iter().chain([&first]).map(|sample| {
+                let normal = Vec3::Y.cross(sample.tangent).normalize_or_zero();
+                sample.point + lift + normal * side * sample.width / 2f32
+            }),
+            Color::RED,
+        );
+    }
+}
@@ -32,6 +32,10 @@ pub struct CameraDebug;
 #[derive(Component)]
 pub struct SpeedDebug;
 
+#[cfg(feature = "debug_screen_colliders")]
+#[derive(Component)]
+pub struct CollidersDebug;
+
 pub struct ScreenDebugPlugin;
 
 fn layout_text(mut commands: Commands) {
@@ -80,6 +84,14 @@ fn layout_text(mut commands: Commands) {
 
         commands.spawn((SpeedDebug, text_bundle));
     }
+
+    #[cfg(feature = "debug_screen_colliders")]
+    {
+        let text_bundle = TextBundle::from_sections([TextSection::new("", default_text_style())])
+            .with_style(get_offset_style());
+
+        commands.spawn((CollidersDebug, text_bundle));
+    }
 }
 
 impl Plugin for ScreenDebugPlugin {
@@ -100,6 +112,8 @@ impl Plugin for ScreenDebugPlugin {
                 camera_debug,
                 #[cfg(feature = "debug_screen_speed")]
                 speed_debug,
+                #[cfg(feature = "debug_screen_colliders")]
+                colliders_debug,
             )
                 .run_if(in_state(crate::AssetLoadingState::Done)),
         );
@@ -125,9 +139,9 @@ pub fn fps_debug(diagnostics: Res<DiagnosticsStore>, mut query: Query<&mut Text,
 #[cfg(feature = "debug_screen_position")]
 pub fn position_debug(
     mut query: Query<&mut Text, With<PositionDebug>>,
-    player: Query<(&Transform, &crate::kart::Kart), Without<PositionDebug>>,
+    player: Query<&Transform, With<crate::kart::Player>>,
 ) {
-    let (player_transform, _) = player.single();
+    let player_transform = player.single();
 
     let mut text_query = query.single_mut();
     let text = text_query.as_mut();
@@ -155,9 +169,9 @@ pub fn camera_debug(
 #[cfg(feature = "debug_screen_speed")]
 pub fn speed_debug(
     mut query: Query<&mut Text, With<SpeedDebug>>,
-    player: Query<(&crate::kart::Speed, &crate::kart::Kart), Without<SpeedDebug>>,
+    player: Query<&crate::kart::Speed, With<crate::kart::Player>>,
 ) {
-    let (player_speed, _) = player.single();
+    let player_speed = player.single();
 
     let mut text_query = query.single_mut();
     let text = text_query.as_mut();
@@ -167,3 +181,29 @@ pub fn speed_debug(
     text.sections[0].value =
         format!("Kart Speed: {current_speed:.2}m/s | Acceleration {acceleration:.2}m/s2");
 }
+
+#[cfg(feature = "debug_screen_colliders")]
+pub fn colliders_debug(
+    mut query: Query<&mut Text, With<CollidersDebug>>,
+    errors: Query<(
+        Option<&Name>,
+        &crate::physics::collider::ColliderFromMeshError,
+    )>,
+) {
+    let mut text_query = query.single_mut();
+    let text = text_query.as_mut();
+
+    let errors: Vec<String> = errors
+        .iter()
+        .map(|(name, error)| format!("{}: {error}", name.map_or("unnamed", Name::as_str)))
+        .collect();
+    text.sections[0].value = if errors.is_empty() {
+        "Colliders: OK".to_string()
+    } else {
+        format!(
+            "Colliders: {} errors | {}",
+            errors.len(),
+            errors.join(" | ")
+        )
+    };
+}
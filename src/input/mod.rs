@@ -3,6 +3,11 @@ use std::collections::HashMap;
 use bevy::{prelude::*, reflect::TypePath};
 use leafwing_input_manager::prelude::*;
 
+use crate::{
+    kart::{KartInput, Player},
+    race::state::RaceState,
+};
+
 mod camera;
 
 #[cfg(feature = "cheat_input_target")]
@@ -17,6 +22,8 @@ pub enum Action {
     Left,
     Right,
     Jump,
+    Drift,
+    Respawn,
 
     #[cfg(feature = "cheat")]
     ChangeInputTarget,
@@ -87,6 +94,44 @@ pub fn get_axis_input(action_state: &Res<ActionState<Action>>) -> (f32, f32) {
     (velocity, steering)
 }
 
+/// Fills the [`KartInput`] of the [`Player`]'s kart from the pressed actions
+///
+/// The kart gets no input outside of [`RaceState::Racing`], or when the inputs
+/// are sent to something else than the kart
+pub fn update_player_input(
+    #[cfg(feature = "cheat_input_target")] input_target: Res<InputTarget>,
+    action_state: Res<ActionState<Action>>,
+    race_state: Res<State<RaceState>>,
+    mut query: Query<&mut KartInput, With<Player>>,
+) {
+    let is_driving = *race_state.get() == RaceState::Racing;
+    #[cfg(feature = "cheat_input_target")]
+    let is_driving = is_driving && *input_target == InputTarget::Kart;
+
+    let input = if is_driving {
+        let (throttle, steering) = get_axis_input(&action_state);
+        KartInput {
+            throttle,
+            steering,
+            jump: action_state.just_pressed(Action::Jump),
+            drift: action_state.pressed(Action::Drift),
+            respawn: action_state.just_pressed(Action::Respawn),
+        }
+    } else {
+        KartInput::default()
+    };
+
+    for mut kart_input in query.iter_mut() {
+        // Presses are kept until a physics step uses them, see
+        // `kart::clear_presses`
+        *kart_input = KartInput {
+            jump: input.jump || kart_input.jump,
+            respawn: input.respawn || kart_input.respawn,
+            ..input
+        };
+    }
+}
+
 #[cfg(feature = "cheat_input_target")]
 pub fn change_input_target(
     mut input_target: ResMut<camera::InputTarget>,
@@ -105,17 +150,29 @@ pub fn change_input_target(
 #[cfg(feature = "cheat_kart_change")]
 pub fn change_kart(
     action_state: Res<ActionState<Action>>,
-    kart_assets: Res<crate::assets::KartAssets>,
-    mut query: Query<(&mut Handle<Scene>, &mut crate::kart::KartVariants)>,
+    kart_registry: Res<crate::kart::registry::KartRegistry>,
+    kart_stats: Res<Assets<crate::kart::stats::KartStats>>,
+    mut query: Query<
+        (
+            &mut Handle<Scene>,
+            &mut crate::kart::registry::KartId,
+            &mut crate::kart::Kart,
+        ),
+        With<Player>,
+    >,
 ) {
     if action_state.just_pressed(Action::ChangeKart) {
-        let (mut scene_handle, mut kart_variant) = query.single_mut();
-        let scene_mut = scene_handle.as_mut();
-        let kart_variant = kart_variant.as_mut();
+        let (mut scene_handle, mut kart_id, mut kart) = query.single_mut();
+
+        let kart_entry = kart_registry.next(&kart_id);
+        *kart_id = kart_entry.id.clone();
+        *scene_handle = kart_entry.scene.clone();
 
-        *kart_variant = kart_variant.next();
-        *scene_mut = kart_variant.get_handle(&kart_assets);
+        let stats = kart_stats
+            .get(&kart_entry.stats)
+            .expect("Kart stats are loaded with the kart assets");
+        *kart = crate::kart::Kart::from_stats(stats);
 
-        tracing::info!("Changing kart to {kart_variant:?}");
+        tracing::info!("Changing kart to {}", kart_entry.name);
     }
 }
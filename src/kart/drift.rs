@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+
+use super::{jump::GroundState, Kart, KartInput, Speed};
+
+/// Drift and mini-turbo tuning of a kart
+#[derive(Debug, Clone, Copy)]
+pub struct DriftSettings {
+    /// Minimum speed to start or keep drifting, in meters per second
+    pub min_speed: f32,
+    /// Fraction of the max steering angle used when steering into the drift
+    pub inner_steering: f32,
+    /// Fraction of the max steering angle used when counter-steering
+    ///
+    /// The kart always turns into the drift, counter-steering widens the turn
+    pub counter_steering: f32,
+    /// Angle in radians between the kart's nose and its direction of travel
+    pub slip_angle: f32,
+    /// Drift time in seconds needed to reach the blue, orange and purple tiers
+    pub tier_charge: [f32; 3],
+    /// Boost duration in seconds granted by the blue, orange and purple tiers
+    pub tier_boost_duration: [f32; 3],
+    /// Speed allowed above `Kart::max_speed` while boosting, in meters per second
+    pub boost_speed: f32,
+    /// Acceleration given while boosting in meters per second squared
+    pub boost_acceleration: f32,
+}
+
+impl Default for DriftSettings {
+    fn default() -> Self {
+        Self {
+            min_speed: 4f32,
+            inner_steering: 1f32,
+            counter_steering: 0.3f32,
+            slip_angle: 20f32.to_radians(),
+            tier_charge: [0.7f32, 1.5f32, 2.5f32],
+            tier_boost_duration: [0.5f32, 1f32, 1.5f32],
+            boost_speed: 4f32,
+            boost_acceleration: 15f32,
+        }
+    }
+}
+
+/// Mini-turbo charge level of a drift
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum DriftTier {
+    #[default]
+    None,
+    Blue,
+    Orange,
+    Purple,
+}
+
+impl DriftTier {
+    const TIERS: [DriftTier; 3] = [DriftTier::Blue, DriftTier::Orange, DriftTier::Purple];
+
+    /// Highest tier reached after drifting for `charge` seconds
+    fn from_charge(charge: f32, settings: &DriftSettings) -> Self {
+        Self::TIERS
+            .into_iter()
+            .zip(settings.tier_charge)
+            .rev()
+            .find(|(_, tier_charge)| charge >= *tier_charge)
+            .map_or(DriftTier::None, |(tier, _)| tier)
+    }
+
+    /// Boost duration in seconds granted when releasing a drift at this tier
+    fn boost_duration(&self, settings: &DriftSettings) -> f32 {
+        match *self {
+            DriftTier::None => 0f32,
+            DriftTier::Blue => settings.tier_boost_duration[0],
+            DriftTier::Orange => settings.tier_boost_duration[1],
+            DriftTier::Purple => settings.tier_boost_duration[2],
+        }
+    }
+}
+
+/// Present on a kart while it drifts
+#[derive(Debug, Component)]
+pub struct Drift {
+    /// Side of the drift, 1 for left and -1 for right, like the steering input
+    pub direction: f32,
+    /// Time spent drifting on the ground in seconds
+    pub charge: f32,
+    pub tier: DriftTier,
+}
+
+impl Drift {
+    /// Steering angle in radians while drifting
+    pub fn steering_angle(&self, kart: &Kart, input_steering: f32) -> f32 {
+        // 0 when counter-steering, 1 when steering into the drift
+        let into_drift = (input_steering * self.direction + 1f32) / 2f32;
+        let settings = &kart.drift;
+        let steering = settings.counter_steering
+            + (settings.inner_steering - settings.counter_steering) * into_drift;
+
+        self.direction * steering * kart.max_steering_angle
+    }
+
+    /// Direction the kart travels towards, in the kart's local space
+    ///
+    /// While drifting, the kart's nose points into the turn
+    pub fn local_travel_direction(drift: Option<&Self>, kart: &Kart) -> Vec3 {
+        match drift {
+            Some(drift) => {
+                Quat::from_rotation_y(-drift.direction * kart.drift.slip_angle) * Vec3::Z
+            }
+            None => Vec3::Z,
+        }
+    }
+}
+
+/// Present on a kart while a mini-turbo is active
+#[derive(Debug, Component)]
+pub struct Boost {
+    /// Remaining boost time in seconds
+    pub remaining: f32,
+}
+
+/// Starts, charges and releases drifts from [`KartInput::drift`]
+#[allow(clippy::type_complexity)]
+pub fn update_drift(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    mut query: Query<(
+        Entity,
+        &GroundState,
+        &Speed,
+        &Kart,
+        &KartInput,
+        Option<&mut Drift>,
+    )>,
+) {
+    for (entity, ground_state, speed, kart, input, drift) in query.iter_mut() {
+        let settings = &kart.drift;
+        let is_fast_enough = speed.forward_speed >= settings.min_speed;
+
+        let Some(mut drift) = drift else {
+            if input.drift && input.steering != 0f32 && ground_state.is_grounded() && is_fast_enough
+            {
+                commands.entity(entity).insert(Drift {
+                    direction: input.steering.signum(),
+                    charge: 0f32,
+                    tier: DriftTier::None,
+                });
+            }
+            continue;
+        };
+
+        // Slowing down too much cancels the drift without a boost
+        if !is_fast_enough {
+            commands.entity(entity).remove::<Drift>();
+            continue;
+        }
+
+        if !input.drift {
+            commands.entity(entity).remove::<Drift>();
+
+            if drift.tier != DriftTier::None {
+                tracing::debug!("Mini-turbo {:?}", drift.tier);
+                commands.entity(entity).insert(Boost {
+                    remaining: drift.tier.boost_duration(settings),
+                });
+            }
+            continue;
+        }
+
+        if ground_state.is_grounded() {
+            drift.charge += fixed_time.period.as_secs_f32();
+            drift.tier = DriftTier::from_charge(drift.charge, settings);
+        }
+    }
+}
+
+pub fn update_boost(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    mut query: Query<(Entity, &mut Boost)>,
+) {
+    for (entity, mut boost) in query.iter_mut() {
+        boost.remaining -= fixed_time.period.as_secs_f32();
+        if boost.remaining <= 0f32 {
+            commands.entity(entity).remove::<Boost>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tier_is_reached_at_its_charge() {
+        let settings = DriftSettings::default();
+        let [blue, orange, purple] = settings.tier_charge;
+
+        assert_eq!(DriftTier::from_charge(0f32, &settings), DriftTier::None);
+        assert_eq!(
+            DriftTier::from_charge(blue - 0.01f32, &settings),
+            DriftTier::None
+        );
+        assert_eq!(DriftTier::from_charge(blue, &settings), DriftTier::Blue);
+        assert_eq!(
+            DriftTier::from_charge(orange - 0.01f32, &settings),
+            DriftTier::Blue
+        );
+        assert_eq!(DriftTier::from_charge(orange, &settings), DriftTier::Orange);
+        assert_eq!(DriftTier::from_charge(purple, &settings), DriftTier::Purple);
+        assert_eq!(
+            DriftTier::from_charge(10f32 * purple, &settings),
+            DriftTier::Purple
+        );
+    }
+
+    #[test]
+    fn boost_lasts_longer_for_higher_tiers() {
+        let settings = DriftSettings::default();
+
+        assert_eq!(DriftTier::None.boost_duration(&settings), 0f32);
+        assert_eq!(
+            DriftTier::Blue.boost_duration(&settings),
+            settings.tier_boost_duration[0]
+        );
+        assert_eq!(
+            DriftTier::Orange.boost_duration(&settings),
+            settings.tier_boost_duration[1]
+        );
+        assert_eq!(
+            DriftTier::Purple.boost_duration(&settings),
+            settings.tier_boost_duration[2]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{suspension::Wheel, Kart, KartInput};
+
+/// Whether the kart touches the ground, updated from its wheels
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub enum GroundState {
+    /// At least one wheel touches the ground
+    Grounded,
+    /// No wheel touches the ground
+    Airborne {
+        /// Time spent in the air in seconds
+        air_time: f32,
+    },
+}
+
+impl Default for GroundState {
+    fn default() -> Self {
+        // Karts are spawned slightly above the ground
+        Self::Airborne { air_time: 0f32 }
+    }
+}
+
+impl GroundState {
+    pub fn is_grounded(&self) -> bool {
+        matches!(self, Self::Grounded)
+    }
+}
+
+/// Sent when a kart touches the ground after being airborne
+#[derive(Debug, Event)]
+pub struct KartLanded {
+    pub kart: Entity,
+    /// Speed at which the kart hit the ground along its up axis, in meters per second
+    pub impact: f32,
+}
+
+pub fn update_ground_state(
+    fixed_time: Res<FixedTime>,
+    mut karts: Query<(Entity, &GlobalTransform, &Velocity, &mut GroundState)>,
+    wheels: Query<&Wheel>,
+    mut landed_events: EventWriter<KartLanded>,
+) {
+    for (entity, transform, velocity, mut ground_state) in karts.iter_mut() {
+        let is_grounded = wheels
+            .iter()
+            .any(|wheel| wheel.kart == entity && wheel.is_grounded);
+
+        *ground_state = match (*ground_state, is_grounded) {
+            (GroundState::Grounded, true) => continue,
+            (GroundState::Grounded, false) => GroundState::Airborne { air_time: 0f32 },
+            (GroundState::Airborne { air_time }, false) => GroundState::Airborne {
+                air_time: air_time + fixed_time.period.as_secs_f32(),
+            },
+            (GroundState::Airborne { air_time }, true) => {
+                let impact = (-velocity.linvel.dot(transform.up())).max(0f32);
+                landed_events.send(KartLanded {
+                    kart: entity,
+                    impact,
+                });
+
+                tracing::debug!("Kart landed after {air_time:.2}s at {impact:.2}m/s");
+                GroundState::Grounded
+            }
+        };
+    }
+}
+
+/// Makes the kart hop on [`KartInput::jump`] while on the ground
+pub fn jump(
+    mut query: Query<(
+        &GlobalTransform,
+        &ReadMassProperties,
+        &GroundState,
+        &KartInput,
+        &Kart,
+        &mut ExternalImpulse,
+    )>,
+) {
+    for (transform, mass_properties, ground_state, input, kart, mut external_impulse) in
+        query.iter_mut()
+    {
+        if !input.jump || !ground_state.is_grounded() {
+            continue;
+        }
+
+        external_impulse.impulse += transform.up() * kart.jump_velocity * mass_properties.0.mass;
+    }
+}
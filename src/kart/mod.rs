@@ -0,0 +1,389 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+pub mod drift;
+pub mod jump;
+pub mod registry;
+pub mod stats;
+pub mod suspension;
+
+use drift::{Boost, Drift, DriftSettings};
+use jump::GroundState;
+use suspension::{Suspension, Wheel};
+
+#[derive(Debug, Component)]
+pub struct Speed {
+    /// Acceleration in meters per second squared
+    pub acceleration: f32,
+    /// Current speed in meters per second
+    pub forward_speed: f32,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self {
+            acceleration: 0f32,
+            forward_speed: 0f32,
+        }
+    }
+}
+
+/// Marks the kart driven by the player
+#[derive(Debug, Component)]
+pub struct Player;
+
+/// What the kart is asked to do this frame, by the player or anything else
+/// driving it
+#[derive(Debug, Component, Clone, Copy, Default, PartialEq)]
+pub struct KartInput {
+    /// From -1 (full reverse) to 1 (full throttle)
+    pub throttle: f32,
+    /// From -1 (full right) to 1 (full left)
+    pub steering: f32,
+    /// Whether the kart should jump, until the next physics step, see
+    /// [`clear_presses`]
+    pub jump: bool,
+    /// Whether the kart should drift, for as long as the drift lasts
+    pub drift: bool,
+    /// Whether the kart should be put back on the track, until the next
+    /// physics step, see [`clear_presses`]
+    pub respawn: bool,
+}
+
+/// Forgets the [`KartInput::jump`] and [`KartInput::respawn`] presses once a
+/// physics step used them
+///
+/// The input is read on every frame and the karts are driven on the
+/// [`FixedUpdate`] steps, a frame can have no step or several of them
+pub fn clear_presses(mut inputs: Query<&mut KartInput>) {
+    for mut input in inputs.iter_mut() {
+        input.jump = false;
+        input.respawn = false;
+    }
+}
+
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct FrontWheels;
+
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct BackWheels;
+
+#[derive(Debug, Component)]
+pub struct Kart {
+    /// Maximum speed in meters per second
+    pub max_speed: f32,
+    /// Minimum speed in meters per second
+    pub min_speed: f32,
+    /// Max steering angle in radians
+    pub max_steering_angle: f32,
+    /// Distance between the front and back wheels
+    pub wheel_distance: f32,
+    /// Weight in kilograms, given to the chassis collider
+    pub weight: f32,
+    /// Engine acceleration at a standstill in meters per second squared
+    pub acceleration: f32,
+    /// Shape of the acceleration falloff when nearing `max_speed`
+    ///
+    /// The engine delivers `acceleration * (1 - (speed / max_speed) ^ acceleration_curve)`,
+    /// 1 means a linear falloff, higher values keep the kart punchy until top speed
+    pub acceleration_curve: f32,
+    /// Engine acceleration when reversing in meters per second squared
+    pub reverse_acceleration: f32,
+    /// Deceleration when braking in meters per second squared
+    pub braking_deceleration: f32,
+    /// Constant deceleration while rolling in meters per second squared
+    pub rolling_resistance: f32,
+    /// Air drag coefficient, the drag deceleration is `air_drag * speed^2`
+    pub air_drag: f32,
+    /// Suspension of the wheels marked with [`FrontWheels`]
+    pub front_suspension: Suspension,
+    /// Suspension of the wheels marked with [`BackWheels`]
+    pub back_suspension: Suspension,
+    /// Vertical speed given by a jump in meters per second
+    pub jump_velocity: f32,
+    /// Fraction of the steering that is kept while airborne, from 0 to 1
+    pub air_steering: f32,
+    /// Drift and mini-turbo tuning
+    pub drift: DriftSettings,
+}
+
+impl Default for Kart {
+    fn default() -> Self {
+        Self {
+            max_speed: 10f32,
+            min_speed: -5f32,
+            max_steering_angle: 30f32.to_radians(),
+            wheel_distance: 2f32,
+            weight: 300f32,
+            acceleration: 8f32,
+            acceleration_curve: 2f32,
+            reverse_acceleration: 5f32,
+            braking_deceleration: 20f32,
+            rolling_resistance: 0.5f32,
+            air_drag: 0.01f32,
+            front_suspension: Suspension::default(),
+            back_suspension: Suspension::default(),
+            jump_velocity: 4f32,
+            air_steering: 0.3f32,
+            drift: DriftSettings::default(),
+        }
+    }
+}
+
+/// Speed under which the kart is considered stopped, in meters per second
+const STOPPED_SPEED: f32 = 0.1f32;
+
+impl Kart {
+    /// Acceleration along the kart's forward axis for the given `speed` and
+    /// `throttle` input, from -1 (full reverse) to 1 (full throttle)
+    ///
+    /// Pushing against the current direction of travel brakes, and only once
+    /// the kart is stopped does it start going the other way
+    pub fn acceleration(&self, speed: f32, throttle: f32) -> f32 {
+        let is_moving = speed.abs() > STOPPED_SPEED;
+
+        let engine = if throttle == 0f32 {
+            0f32
+        } else if is_moving && speed.signum() != throttle.signum() {
+            throttle.abs() * self.braking_deceleration * -speed.signum()
+        } else if throttle > 0f32 {
+            let ratio = (speed / self.max_speed).clamp(0f32, 1f32);
+            throttle * self.acceleration * (1f32 - ratio.powf(self.acceleration_curve))
+        } else {
+            let ratio = (speed / self.min_speed).clamp(0f32, 1f32);
+            throttle * self.reverse_acceleration * (1f32 - ratio.powf(self.acceleration_curve))
+        };
+
+        let drag = if is_moving {
+            -speed.signum() * (self.rolling_resistance + self.air_drag * speed * speed)
+        } else {
+            0f32
+        };
+
+        engine + drag
+    }
+}
+
+/// Half extents of the box used as the kart's chassis collider
+pub const CHASSIS_HALF_EXTENTS: Vec3 = Vec3::new(1f32, 0.4f32, 2.2f32);
+/// Height of the bottom of the chassis collider, the kart is held above the
+/// ground by its suspension, see [`suspension::update_suspension`]
+const CHASSIS_CLEARANCE: f32 = 0.4f32;
+
+/// Marks the chassis collider of a kart
+#[derive(Debug, Component)]
+pub struct Chassis;
+
+/// The chassis collider of a kart, meant to be spawned as a child of the
+/// kart's `RigidBody`
+///
+/// Its mass is kept in sync with [`Kart::weight`] by [`update_chassis_weight`]
+pub fn chassis_collider() -> (Chassis, Collider, Friction, TransformBundle) {
+    (
+        Chassis,
+        Collider::cuboid(
+            CHASSIS_HALF_EXTENTS.x,
+            CHASSIS_HALF_EXTENTS.y,
+            CHASSIS_HALF_EXTENTS.z,
+        ),
+        // Traction is handled by `update_kart_position`, friction would only
+        // slow the kart down and make it stick to walls
+        Friction {
+            coefficient: 0f32,
+            combine_rule: CoefficientCombineRule::Min,
+        },
+        TransformBundle::from_transform(Transform::from_xyz(
+            0f32,
+            CHASSIS_CLEARANCE + CHASSIS_HALF_EXTENTS.y,
+            0f32,
+        )),
+    )
+}
+
+pub fn update_chassis_weight(
+    mut commands: Commands,
+    karts: Query<(&Kart, &Children), Changed<Kart>>,
+    chassis: Query<Entity, With<Chassis>>,
+) {
+    for (kart, children) in karts.iter() {
+        for chassis in chassis.iter_many(children) {
+            commands
+                .entity(chassis)
+                .insert(ColliderMassProperties::Mass(kart.weight));
+        }
+    }
+}
+
+/// Integrates the kart's [`Speed`] from the engine, brakes and drag
+///
+/// The integration starts from the actual velocity of the rigid body, so
+/// hitting a wall or climbing a hill slows the kart down
+#[allow(clippy::type_complexity)]
+pub fn update_kart_speed(
+    fixed_time: Res<FixedTime>,
+    mut query: Query<(
+        &Transform,
+        &Velocity,
+        &GroundState,
+        &KartInput,
+        &mut Speed,
+        &Kart,
+        Option<&Drift>,
+        Option<&Boost>,
+    )>,
+) {
+    for (transform, velocity, ground_state, input, mut speed, kart, drift, boost) in
+        query.iter_mut()
+    {
+        // Wheels in the air can't push the kart
+        let input_velocity = if ground_state.is_grounded() {
+            input.throttle
+        } else {
+            0f32
+        };
+
+        let travel_direction = transform.rotation * Drift::local_travel_direction(drift, kart);
+        let current_speed = velocity.linvel.dot(travel_direction);
+
+        let mut acceleration = kart.acceleration(current_speed, input_velocity);
+        let mut max_speed = kart.max_speed;
+        if boost.is_some() {
+            acceleration += kart.drift.boost_acceleration;
+            max_speed += kart.drift.boost_speed;
+        }
+
+        let mut forward_speed = current_speed + acceleration * fixed_time.period.as_secs_f32();
+
+        // Brakes and drag stop the kart, they never make it go the other way
+        let is_pushing_forward = input_velocity * forward_speed > 0f32;
+        if forward_speed * current_speed < 0f32 && !is_pushing_forward {
+            forward_speed = 0f32;
+        }
+
+        // The engine can't go past the speed limits, but the kart keeps its momentum
+        // when it already goes faster (e.g. after a boost)
+        speed.acceleration = acceleration;
+        speed.forward_speed = forward_speed.clamp(
+            kart.min_speed.min(current_speed),
+            max_speed.max(current_speed),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_kart_position(
+    mut query: Query<(
+        &Transform,
+        &mut Velocity,
+        &GroundState,
+        &KartInput,
+        &Speed,
+        &Kart,
+        Option<&Drift>,
+    )>,
+) {
+    // Basic algorithm from http://engineeringdotnet.blogspot.com/2010/04/simple-2d-car-physics-in-games.html
+    for (transform, mut velocity, ground_state, input, speed, kart, drift) in query.iter_mut() {
+        let forward_speed = speed.forward_speed;
+        let mut steering_angle = match drift {
+            Some(drift) => drift.steering_angle(kart, input.steering),
+            None => input.steering * kart.max_steering_angle,
+        };
+        if !ground_state.is_grounded() {
+            steering_angle *= kart.air_steering;
+        }
+
+        // Up is +Y in the kart's local space, front is +Z unless drifting
+        let travel_direction = transform.rotation * Drift::local_travel_direction(drift, kart);
+        let up = transform.rotation * Vec3::Y;
+
+        // With the rear wheel following the front wheel, the kart turns around its
+        // up axis at `speed * tan(steering_angle) / wheel_distance` radians per second
+        let yaw_rate = forward_speed * steering_angle.tan() / kart.wheel_distance;
+
+        // The velocity along the kart's up axis is left to Rapier, so gravity,
+        // slopes and collisions still apply. Sideways velocity is dropped (full grip)
+        // In the air, the kart keeps its momentum
+        if ground_state.is_grounded() {
+            let vertical_velocity = up * velocity.linvel.dot(up);
+            velocity.linvel = travel_direction * forward_speed + vertical_velocity;
+        }
+
+        // Same for the angular velocity, only the rotation around the up axis is driven
+        let tilt_velocity = velocity.angvel - up * velocity.angvel.dot(up);
+        velocity.angvel = tilt_velocity + up * yaw_rate;
+    }
+}
+
+pub fn update_front_wheels(
+    mut query: Query<(&mut Transform, &Wheel), With<FrontWheels>>,
+    kart_query: Query<(&Kart, &KartInput)>,
+) {
+    for (mut transform, wheel) in query.iter_mut() {
+        let Ok((kart, input)) = kart_query.get(wheel.kart) else {
+            continue;
+        };
+
+        let steering_angle = input.steering * kart.max_steering_angle;
+        transform.rotation = Quat::from_rotation_y(steering_angle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acceleration_is_full_from_a_standstill() {
+        let kart = Kart::default();
+
+        assert_eq!(kart.acceleration(0f32, 1f32), kart.acceleration);
+        assert_eq!(kart.acceleration(0f32, -1f32), -kart.reverse_acceleration);
+        assert_eq!(kart.acceleration(0f32, 0f32), 0f32);
+    }
+
+    #[test]
+    fn engine_falls_off_at_top_speed() {
+        let kart = Kart::default();
+        let drag = kart.rolling_resistance + kart.air_drag * kart.max_speed * kart.max_speed;
+
+        assert_eq!(kart.acceleration(kart.max_speed, 1f32), -drag);
+
+        // Past top speed, e.g. after a boost, only the drag is left
+        let speed = 2f32 * kart.max_speed;
+        let drag = kart.rolling_resistance + kart.air_drag * speed * speed;
+        assert_eq!(kart.acceleration(speed, 1f32), -drag);
+    }
+
+    #[test]
+    fn throttle_against_travel_brakes() {
+        let kart = Kart::default();
+        let speed = 5f32;
+        let drag = kart.rolling_resistance + kart.air_drag * speed * speed;
+
+        assert_eq!(
+            kart.acceleration(speed, -1f32),
+            -kart.braking_deceleration - drag
+        );
+        assert_eq!(
+            kart.acceleration(-speed, 1f32),
+            kart.braking_deceleration + drag
+        );
+        assert_eq!(
+            kart.acceleration(speed, -0.5f32),
+            -0.5f32 * kart.braking_deceleration - drag
+        );
+    }
+
+    #[test]
+    fn nearly_stopped_kart_changes_direction() {
+        let kart = Kart::default();
+        let speed = STOPPED_SPEED / 2f32;
+
+        // Under the stopped speed there is no braking nor drag
+        assert_eq!(kart.acceleration(speed, -1f32), -kart.reverse_acceleration);
+        assert_eq!(kart.acceleration(-speed, 0f32), 0f32);
+    }
+}
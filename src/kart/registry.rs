@@ -0,0 +1,149 @@
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::HashSet,
+};
+use bevy_asset_loader::prelude::DynamicAssets;
+
+use super::stats::KartStats;
+use crate::assets::{AssetLoadingState, DynamicHandles, ManifestAssets, RonAsset, RonLoader};
+
+/// Identifier of a kart, as written in the kart manifest
+#[derive(
+    Debug,
+    Component,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Deserialize,
+    serde::Serialize
+)]
+#[serde(transparent)]
+pub struct KartId(pub String);
+
+/// A kart of the kart manifest
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KartManifestEntry {
+    pub id: KartId,
+    /// Name shown to the players
+    pub name: String,
+    /// Path to the kart's glTF scene, e.g. `karts/sedan.glb#Scene0`
+    pub scene: String,
+    /// Path to the kart's stats, see [`KartStats`]
+    pub stats: String,
+}
+
+/// Every kart of the game, loaded from a `.karts.ron` file
+#[derive(Debug, serde::Deserialize, TypeUuid, TypePath)]
+#[uuid = "a4e3c0d2-7f0b-4a53-9b8e-61c2f1d9e7a4"]
+#[serde(deny_unknown_fields)]
+pub struct KartManifest {
+    pub karts: Vec<KartManifestEntry>,
+}
+
+impl RonAsset for KartManifest {
+    type Error = String;
+
+    const NAME: &'static str = "kart manifest";
+    const EXTENSIONS: &'static [&'static str] = &["karts.ron"];
+
+    fn validate(&self) -> Result<(), String> {
+        if self.karts.is_empty() {
+            return Err("the manifest has no kart".to_string());
+        }
+
+        let mut ids = HashSet::new();
+        for kart in self.karts.iter() {
+            if !ids.insert(&kart.id) {
+                return Err(format!("kart {:?} is declared more than once", kart.id.0));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A kart of the [`KartRegistry`], with its assets
+#[derive(Debug)]
+pub struct KartEntry {
+    pub id: KartId,
+    pub name: String,
+    pub scene: Handle<Scene>,
+    pub stats: Handle<KartStats>,
+}
+
+/// Every kart of the kart manifest, in the manifest's order
+///
+/// Available once [`AssetLoadingState::AssetLoading`] is entered, the assets
+/// are loaded when reaching [`AssetLoadingState::Done`]
+#[derive(Debug, Resource)]
+pub struct KartRegistry {
+    karts: Vec<KartEntry>,
+}
+
+impl KartRegistry {
+    /// The first kart of the manifest
+    pub fn default_kart(&self) -> &KartEntry {
+        // The manifest is validated to have at least one kart
+        &self.karts[0]
+    }
+
+    pub fn get(&self, id: &KartId) -> Option<&KartEntry> {
+        self.karts.iter().find(|kart| kart.id == *id)
+    }
+
+    /// The kart after `id` in the manifest, wrapping around
+    pub fn next(&self, id: &KartId) -> &KartEntry {
+        let index = self.karts.iter().position(|kart| kart.id == *id);
+        let next_index = index.map_or(0, |index| (index + 1) % self.karts.len());
+
+        &self.karts[next_index]
+    }
+}
+
+/// Builds the [`KartRegistry`] from the loaded manifest and registers the karts'
+/// assets to be loaded in [`AssetLoadingState::AssetLoading`]
+fn register_karts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    manifest_assets: Res<ManifestAssets>,
+    manifests: Res<Assets<KartManifest>>,
+    mut dynamic_assets: ResMut<DynamicAssets>,
+) {
+    let manifest = manifests
+        .get(&manifest_assets.karts)
+        .expect("Kart manifest is loaded with the manifest assets");
+
+    let karts: Vec<KartEntry> = manifest
+        .karts
+        .iter()
+        .map(|kart| KartEntry {
+            id: kart.id.clone(),
+            name: kart.name.clone(),
+            scene: asset_server.load(&kart.scene),
+            stats: asset_server.load(&kart.stats),
+        })
+        .collect();
+
+    let handles = karts
+        .iter()
+        .flat_map(|kart| [kart.scene.clone_untyped(), kart.stats.clone_untyped()])
+        .collect();
+    dynamic_assets.register_asset("karts", Box::new(DynamicHandles(handles)));
+
+    tracing::info!("Registered {} karts", karts.len());
+    commands.insert_resource(KartRegistry { karts });
+}
+
+pub struct KartRegistryPlugin;
+
+impl Plugin for KartRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<KartManifest>();
+        app.init_asset_loader::<RonLoader<KartManifest>>();
+
+        app.add_systems(OnExit(AssetLoadingState::Manifests), register_karts);
+    }
+}
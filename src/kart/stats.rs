@@ -0,0 +1,207 @@
+use std::fmt;
+
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+
+use super::Kart;
+use crate::assets::{RonAsset, RonLoader};
+
+/// Stats of a kart variant, loaded from a `.kart.ron` file
+///
+/// Every stat is required, tuning that is shared by all karts lives in
+/// [`Kart::default`]
+#[derive(Debug, serde::Deserialize, TypeUuid, TypePath)]
+#[uuid = "5b1f4ab4-9a4e-4d0c-8f7c-2d5e8c1f6a3b"]
+#[serde(deny_unknown_fields)]
+pub struct KartStats {
+    /// Top speed in meters per second
+    pub max_speed: f32,
+    /// Top speed when reversing in meters per second
+    pub reverse_speed: f32,
+    /// Engine acceleration at a standstill in meters per second squared
+    pub acceleration: f32,
+    /// Weight in kilograms
+    pub weight: f32,
+    /// Max steering angle in degrees
+    pub handling: f32,
+    /// Distance between the front and back wheels in meters
+    pub wheel_base: f32,
+    /// Vertical impulse of a jump in newton seconds
+    pub jump_impulse: f32,
+}
+
+#[derive(Debug)]
+pub enum KartStatsError {
+    OutOfRange {
+        stat: &'static str,
+        value: f32,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for KartStatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KartStatsError::OutOfRange {
+                stat,
+                value,
+                expected,
+            } => write!(f, "`{stat}` is {value}, expected {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for KartStatsError {}
+
+impl RonAsset for KartStats {
+    type Error = KartStatsError;
+
+    const NAME: &'static str = "kart stats";
+    const EXTENSIONS: &'static [&'static str] = &["kart.ron"];
+
+    fn validate(&self) -> Result<(), KartStatsError> {
+        let positive_stats = [
+            ("max_speed", self.max_speed),
+            ("reverse_speed", self.reverse_speed),
+            ("acceleration", self.acceleration),
+            ("weight", self.weight),
+            ("wheel_base", self.wheel_base),
+            ("jump_impulse", self.jump_impulse),
+        ];
+
+        for (stat, value) in positive_stats {
+            if !value.is_finite() || value <= 0f32 {
+                return Err(KartStatsError::OutOfRange {
+                    stat,
+                    value,
+                    expected: "a positive number",
+                });
+            }
+        }
+
+        if !self.handling.is_finite() || self.handling <= 0f32 || self.handling >= 90f32 {
+            return Err(KartStatsError::OutOfRange {
+                stat: "handling",
+                value: self.handling,
+                expected: "an angle between 0 and 90 degrees",
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Kart {
+    pub fn from_stats(stats: &KartStats) -> Self {
+        Self {
+            max_speed: stats.max_speed,
+            min_speed: -stats.reverse_speed,
+            max_steering_angle: stats.handling.to_radians(),
+            wheel_distance: stats.wheel_base,
+            acceleration: stats.acceleration,
+            weight: stats.weight,
+            jump_velocity: stats.jump_impulse / stats.weight,
+            ..default()
+        }
+    }
+}
+
+pub struct KartStatsPlugin;
+
+impl Plugin for KartStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<KartStats>();
+        app.init_asset_loader::<RonLoader<KartStats>>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_stats() -> KartStats {
+        KartStats {
+            max_speed: 10f32,
+            reverse_speed: 5f32,
+            acceleration: 8f32,
+            weight: 300f32,
+            handling: 30f32,
+            wheel_base: 2f32,
+            jump_impulse: 1200f32,
+        }
+    }
+
+    fn invalid_stat(stats: KartStats) -> Option<&'static str> {
+        match stats.validate() {
+            Err(KartStatsError::OutOfRange { stat, .. }) => Some(stat),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn shipped_stats_are_valid() {
+        for content in [
+            include_str!("../../assets/karts/monster_truck.kart.ron"),
+            include_str!("../../assets/karts/muscle.kart.ron"),
+            include_str!("../../assets/karts/police.kart.ron"),
+            include_str!("../../assets/karts/sedan.kart.ron"),
+            include_str!("../../assets/karts/sports.kart.ron"),
+        ] {
+            let stats: KartStats = ron::from_str(content).unwrap();
+            assert!(stats.validate().is_ok(), "{stats:?}");
+        }
+    }
+
+    #[test]
+    fn stats_must_be_positive() {
+        assert!(valid_stats().validate().is_ok());
+
+        for value in [0f32, -1f32, f32::NAN, f32::INFINITY] {
+            let stats = KartStats {
+                max_speed: value,
+                ..valid_stats()
+            };
+            assert_eq!(invalid_stat(stats), Some("max_speed"));
+
+            let stats = KartStats {
+                weight: value,
+                ..valid_stats()
+            };
+            assert_eq!(invalid_stat(stats), Some("weight"));
+
+            let stats = KartStats {
+                jump_impulse: value,
+                ..valid_stats()
+            };
+            assert_eq!(invalid_stat(stats), Some("jump_impulse"));
+        }
+    }
+
+    #[test]
+    fn handling_is_an_acute_angle() {
+        for handling in [0f32, -10f32, 90f32, 120f32, f32::NAN] {
+            let stats = KartStats {
+                handling,
+                ..valid_stats()
+            };
+            assert_eq!(invalid_stat(stats), Some("handling"));
+        }
+
+        let stats = KartStats {
+            handling: 89f32,
+            ..valid_stats()
+        };
+        assert!(stats.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_stats_are_rejected() {
+        let stats = "max_speed: 10.0, reverse_speed: 5.0, acceleration: 8.0, weight: 300.0, \
+            handling: 30.0, wheel_base: 2.0, jump_impulse: 1200.0";
+
+        assert!(ron::from_str::<KartStats>(&format!("({stats})")).is_ok());
+        assert!(ron::from_str::<KartStats>(&format!("({stats}, top_speed: 12.0)")).is_err());
+    }
+}
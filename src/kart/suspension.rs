@@ -0,0 +1,194 @@
+use bevy::{ecs::query::Has, math::Affine3A, prelude::*, render::primitives::Aabb};
+use bevy_rapier3d::prelude::*;
+
+use super::{BackWheels, FrontWheels, Kart};
+
+/// Spring and damper holding a group of wheels
+///
+/// Forces are expressed as accelerations so karts of any weight sit the same way
+#[derive(Debug, Clone, Copy)]
+pub struct Suspension {
+    /// Length of the spring when no weight is applied, in meters
+    pub rest_length: f32,
+    /// Acceleration one wheel gives to the kart, per meter of compression
+    pub stiffness: f32,
+    /// Acceleration one wheel gives to the kart, per meter per second of compression speed
+    pub damping: f32,
+}
+
+impl Default for Suspension {
+    fn default() -> Self {
+        Self {
+            rest_length: 0.3f32,
+            stiffness: 20f32,
+            damping: 3f32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WheelGroup {
+    Front,
+    Back,
+}
+
+/// A wheel of a kart, added to the entities marked with [`FrontWheels`] or
+/// [`BackWheels`] once the bounds of their meshes are known
+#[derive(Debug, Component)]
+pub struct Wheel {
+    /// The kart this wheel belongs to
+    pub kart: Entity,
+    pub group: WheelGroup,
+    /// Center of the wheel in the kart's local space, with an extended suspension
+    pub anchor: Vec3,
+    /// Radius of the wheel in meters
+    pub radius: f32,
+    /// How much the spring is compressed, from 0 to the suspension's `rest_length`
+    pub compression: f32,
+    /// Whether the wheel touches the ground
+    pub is_grounded: bool,
+    /// Translation of the wheel as authored in the glTF
+    rest_translation: Vec3,
+}
+
+/// Wheels marked in the glTF that are not yet set up
+type NewWheelFilter = (Or<(With<FrontWheels>, With<BackWheels>)>, Without<Wheel>);
+
+/// Adds the [`Wheel`] component to newly spawned wheels
+///
+/// The anchor and radius are taken from the bounds of the wheel's meshes
+/// rather than its transform, some models have their wheels' origin at the
+/// center of the car
+pub fn init_wheels(
+    mut commands: Commands,
+    wheels: Query<(Entity, &Transform, Has<FrontWheels>, &Children), NewWheelFilter>,
+    meshes: Query<(&Aabb, &GlobalTransform)>,
+    parents: Query<&Parent>,
+    karts: Query<&GlobalTransform, With<Kart>>,
+) {
+    for (entity, transform, is_front, children) in wheels.iter() {
+        let Some(kart) = parents
+            .iter_ancestors(entity)
+            .find(|ancestor| karts.contains(*ancestor))
+        else {
+            continue;
+        };
+        let kart_from_world = karts.get(kart).unwrap().affine().inverse();
+
+        let mut min = Vec3::INFINITY;
+        let mut max = Vec3::NEG_INFINITY;
+        for (aabb, mesh_transform) in meshes.iter_many(children) {
+            let (center, half_extents) = (Vec3::from(aabb.center), Vec3::from(aabb.half_extents));
+
+            for corner in [-1f32, 1f32]
+                .into_iter()
+                .flat_map(|x| [-1f32, 1f32].map(|y| (x, y)))
+                .flat_map(|(x, y)| [-1f32, 1f32].map(|z| Vec3::new(x, y, z)))
+            {
+                let corner = mesh_transform.transform_point(center + corner * half_extents);
+                let corner = kart_from_world.transform_point3(corner);
+                min = min.min(corner);
+                max = max.max(corner);
+            }
+        }
+
+        // Bounds are computed after the meshes are spawned, try again next frame
+        if min.cmpgt(max).any() {
+            continue;
+        }
+
+        commands.entity(entity).insert(Wheel {
+            kart,
+            group: if is_front {
+                WheelGroup::Front
+            } else {
+                WheelGroup::Back
+            },
+            anchor: (min + max) / 2f32,
+            radius: (max.y - min.y) / 2f32,
+            compression: 0f32,
+            is_grounded: false,
+            rest_translation: transform.translation,
+        });
+    }
+}
+
+/// Casts a ray down from each wheel's suspension mount, pushes the kart up
+/// with the spring and damper forces and moves the wheel to its compressed
+/// position
+pub fn update_suspension(
+    rapier_context: Res<RapierContext>,
+    mut karts: Query<(
+        &GlobalTransform,
+        &Velocity,
+        &ReadMassProperties,
+        &Kart,
+        &mut ExternalForce,
+    )>,
+    mut wheels: Query<(&mut Wheel, &mut Transform, &Parent)>,
+    wheel_parents: Query<&GlobalTransform>,
+) {
+    for (_, _, _, _, mut external_force) in karts.iter_mut() {
+        *external_force = ExternalForce::default();
+    }
+
+    for (mut wheel, mut wheel_transform, wheel_parent) in wheels.iter_mut() {
+        let Ok((kart_transform, velocity, mass_properties, kart, mut external_force)) =
+            karts.get_mut(wheel.kart)
+        else {
+            continue;
+        };
+
+        let suspension = match wheel.group {
+            WheelGroup::Front => kart.front_suspension,
+            WheelGroup::Back => kart.back_suspension,
+        };
+
+        let up = kart_transform.up();
+        let mount = kart_transform.transform_point(wheel.anchor + Vec3::Y * suspension.rest_length);
+
+        let filter = QueryFilter::default()
+            .exclude_rigid_body(wheel.kart)
+            .exclude_sensors();
+        let hit = rapier_context.cast_ray(
+            mount,
+            -up,
+            suspension.rest_length + wheel.radius,
+            true,
+            filter,
+        );
+
+        let spring_length = hit.map_or(suspension.rest_length, |(_, distance)| {
+            (distance - wheel.radius).max(0f32)
+        });
+        wheel.compression = suspension.rest_length - spring_length;
+        wheel.is_grounded = hit.is_some();
+
+        // The wheel moves up the suspension's ray, in the space of its parent
+        let parent_from_world = wheel_parents
+            .get(wheel_parent.get())
+            .map_or(Affine3A::IDENTITY, |transform| transform.affine().inverse());
+        wheel_transform.translation =
+            wheel.rest_translation + parent_from_world.transform_vector3(up * wheel.compression);
+
+        if !wheel.is_grounded {
+            continue;
+        }
+
+        let mass_properties = mass_properties.0;
+        let center_of_mass = kart_transform.transform_point(mass_properties.local_center_of_mass);
+        let mount_velocity = velocity.linvel + velocity.angvel.cross(mount - center_of_mass);
+        let compression_speed = -mount_velocity.dot(up);
+
+        // The suspension can only push the kart away from the ground
+        let acceleration = (suspension.stiffness * wheel.compression
+            + suspension.damping * compression_speed)
+            .max(0f32);
+
+        *external_force += ExternalForce::at_point(
+            up * acceleration * mass_properties.mass,
+            mount,
+            center_of_mass,
+        );
+    }
+}
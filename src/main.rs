@@ -1,5 +1,5 @@
-use assets::{AssetLoadingState, KartAssets, TerrainAssets};
-use bevy::prelude::*;
+use assets::{AssetLoadingState, KartAssets, ManifestAssets, TrackAssets};
+use bevy::{core_pipeline::Skybox, prelude::*};
 use bevy_asset_loader::loading_state::{LoadingState, LoadingStateAppExt};
 use bevy_gltf_components::ComponentsFromGltfPlugin;
 use bevy_rapier3d::prelude::*;
@@ -11,12 +11,17 @@ use tracing_subscriber::FmtSubscriber;
 #[cfg(all(not(debug_assertions), feature = "dynamic_linking"))]
 compile_error!("Dynamic linking should not supported for release builds");
 
+mod ai;
 mod assets;
 mod camera;
 mod debug;
 mod input;
 mod kart;
 mod physics;
+mod race;
+mod save;
+mod scene;
+mod track;
 
 use crate::input::Action;
 
@@ -49,22 +54,41 @@ fn main() {
     app.add_plugins(ComponentsFromGltfPlugin);
     #[cfg(feature = "debug_axis")]
     app.add_plugins(bevy_debug_grid::DebugGridPlugin::with_floor_grid());
-    app.add_plugins(RapierPhysicsPlugin::<NoUserData>::default());
+    app.add_plugins(physics::FixedStepPhysicsPlugin);
+    app.add_plugins(scene::SceneReadyPlugin);
     app.add_plugins(physics::GltfColliderPlugin);
+    app.add_plugins(kart::stats::KartStatsPlugin);
+    app.add_plugins(kart::registry::KartRegistryPlugin);
+    app.add_plugins(track::registry::TrackRegistryPlugin);
+    app.add_plugins(race::RacePlugin);
     #[cfg(feature = "debug_rapier")]
     app.add_plugins(RapierDebugRenderPlugin::default());
 
     app.add_state::<AssetLoadingState>();
+    app.add_loading_state(
+        LoadingState::new(AssetLoadingState::Manifests)
+            .continue_to_state(AssetLoadingState::AssetLoading)
+            .on_failure_continue_to_state(AssetLoadingState::Failed),
+    );
+    app.add_collection_to_loading_state::<_, ManifestAssets>(AssetLoadingState::Manifests);
     app.add_loading_state(
         LoadingState::new(AssetLoadingState::AssetLoading)
-            .continue_to_state(AssetLoadingState::Done),
+            .continue_to_state(AssetLoadingState::Done)
+            .on_failure_continue_to_state(AssetLoadingState::Failed),
     );
     app.add_collection_to_loading_state::<_, KartAssets>(AssetLoadingState::AssetLoading);
-    app.add_collection_to_loading_state::<_, TerrainAssets>(AssetLoadingState::AssetLoading);
+    app.add_collection_to_loading_state::<_, TrackAssets>(AssetLoadingState::AssetLoading);
 
     app.add_systems(OnEnter(AssetLoadingState::Done), || {
         tracing::info!("Assets loaded!");
     });
+    app.add_systems(OnEnter(AssetLoadingState::Failed), || {
+        tracing::error!("Failed to load assets, see the errors above");
+    });
+
+    app.insert_resource(track::registry::SelectedTrack::from_args());
+    app.insert_resource(race::RaceMode::from_args());
+    app.insert_resource(ai::Difficulty::from_args());
 
     app.init_resource::<ActionState<Action>>();
     app.insert_resource(input_map);
@@ -75,6 +99,13 @@ fn main() {
     // Needed for the `ComponentsFromGltfPlugin`
     app.register_type::<FrontWheels>();
     app.register_type::<BackWheels>();
+    app.register_type::<track::spawn::SpawnPoint>();
+    app.register_type::<track::racing_line::Waypoint>();
+
+    app.init_resource::<track::spawn::SpawnPoints>();
+    app.init_resource::<track::racing_line::RacingLine>();
+
+    app.add_event::<kart::jump::KartLanded>();
 
     app.add_systems(OnEnter(AssetLoadingState::Done), setup);
 
@@ -87,13 +118,44 @@ fn main() {
             input::change_input_target,
             #[cfg(feature = "debug_input")]
             debug::input::report_pressed_actions,
+            #[cfg(feature = "debug_racing_line")]
+            debug::racing_line::draw_racing_line,
             // Normal systems
-            kart::update_kart_position,
+            input::update_player_input,
             kart::update_front_wheels,
-            camera::sync_camera_to_player.after(kart::update_kart_position),
+            kart::update_chassis_weight,
+            track::collider::build_track_collider.in_set(scene::SceneReadySet),
+            track::spawn::collect_spawn_points.in_set(scene::SceneReadySet),
+            track::racing_line::collect_waypoints.in_set(scene::SceneReadySet),
+            camera::sync_camera_to_player,
         )
             .run_if(in_state(AssetLoadingState::Done)),
     );
+    // The karts are driven on the physics steps, so they go the same whatever
+    // the frame rate
+    app.add_systems(
+        FixedUpdate,
+        (
+            ai::drive_ai,
+            race::respawn::detect_out_of_bounds.run_if(
+                in_state(race::state::RaceState::Racing)
+                    .or_else(in_state(race::state::RaceState::Finished)),
+            ),
+            race::respawn::update_respawn,
+            kart::suspension::init_wheels,
+            kart::suspension::update_suspension,
+            kart::jump::update_ground_state,
+            kart::jump::jump,
+            kart::drift::update_drift,
+            kart::drift::update_boost,
+            kart::update_kart_speed,
+            kart::update_kart_position,
+            kart::clear_presses,
+        )
+            .chain()
+            .in_set(physics::PhysicsDriveSet)
+            .run_if(in_state(AssetLoadingState::Done)),
+    );
 
     // Change InputMap clash strategy
     app.insert_resource(ClashStrategy::PrioritizeLongest);
@@ -103,34 +165,52 @@ fn main() {
 
 /// set up a simple 3D scene
 fn setup(
-    terrain_assets: Res<assets::TerrainAssets>,
-    kart_assets: Res<assets::KartAssets>,
+    track: Res<track::registry::Track>,
+    kart_registry: Res<kart::registry::KartRegistry>,
+    kart_stats: Res<Assets<kart::stats::KartStats>>,
+    race_mode: Res<race::RaceMode>,
+    difficulty: Res<ai::Difficulty>,
     mut commands: Commands,
 ) {
-    // plane
-
-    commands.spawn((
+    // track
+    tracing::info!("Spawning the {} track", track.name);
+    let mut track_commands = commands.spawn((
         SceneBundle {
-            scene: terrain_assets.map01.clone(),
+            scene: track.scene.clone(),
             ..default()
         },
         RigidBody::Fixed,
-        Collider::cuboid(10f32, 0.5f32, 10f32),
-        Transform::from_xyz(0.0, -0.5, 0.0),
+        track::TrackScene,
     ));
+    track.collision.insert(&mut track_commands);
+
+    if let Some(music) = &track.music {
+        commands.spawn(AudioBundle {
+            source: music.clone(),
+            settings: PlaybackSettings::LOOP,
+        });
+    }
 
     // player
-    let kart_variant = kart::KartVariants::default();
-    commands.spawn((
-        SceneBundle {
-            scene: kart_variant.get_handle(&kart_assets),
-            transform: Transform::from_xyz(0.0, 0.3f32, 0.0),
-            ..default()
-        },
-        kart::Speed::default(),
-        kart::Kart::default(),
-        kart_variant,
-    ));
+    let kart_entry = kart_registry.default_kart();
+    tracing::info!("Spawning the player in the {}", kart_entry.name);
+    spawn_kart(&mut commands, &track, kart_entry, &kart_stats, 0).insert(kart::Player);
+
+    // opponents
+    if *race_mode == race::RaceMode::Race {
+        let mut opponent_entry = kart_entry;
+        for slot in 1..=ai::OPPONENT_COUNT {
+            opponent_entry = kart_registry.next(&opponent_entry.id);
+            tracing::info!(
+                "Spawning a {:?} opponent in the {}",
+                difficulty,
+                opponent_entry.name
+            );
+            spawn_kart(&mut commands, &track, opponent_entry, &kart_stats, slot)
+                .insert(ai::AiDriver::new(*difficulty));
+        }
+    }
+
     // light
     commands.spawn(PointLightBundle {
         point_light: PointLight {
@@ -143,5 +223,53 @@ fn setup(
     });
 
     // camera
-    commands.spawn((Camera3dBundle::default(), camera::MainCamera::default()));
+    let mut camera = commands.spawn((Camera3dBundle::default(), camera::MainCamera::default()));
+    if let Some(skybox) = &track.skybox {
+        camera.insert(Skybox(skybox.clone()));
+    }
+}
+
+/// Spawns a kart on the `slot` of the starting grid, with no one driving it
+fn spawn_kart<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    track: &track::registry::Track,
+    kart_entry: &kart::registry::KartEntry,
+    kart_stats: &Assets<kart::stats::KartStats>,
+    slot: usize,
+) -> bevy::ecs::system::EntityCommands<'w, 's, 'a> {
+    let stats = kart_stats
+        .get(&kart_entry.stats)
+        .expect("Kart stats are loaded with the kart assets");
+
+    let mut kart_commands = commands.spawn((
+        SceneBundle {
+            scene: kart_entry.scene.clone(),
+            transform: track.spawn_grid.slot(slot),
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Velocity::zero(),
+        ExternalForce::default(),
+        ExternalImpulse::default(),
+        ReadMassProperties::default(),
+        Ccd::enabled(),
+        kart::Speed::default(),
+        kart::jump::GroundState::default(),
+        (
+            race::checkpoint::LapProgress::default(),
+            race::state::GridSlot(slot),
+            race::ranking::RacePosition::default(),
+            race::wrong_way::WrongWay::default(),
+        ),
+        kart::Kart::from_stats(stats),
+        kart::KartInput::default(),
+        // The chassis is the kart's only collider, see `kart::chassis_collider`
+        physics::WithoutMeshColliders,
+        kart_entry.id.clone(),
+    ));
+    kart_commands.with_children(|parent| {
+        parent.spawn(kart::chassis_collider());
+    });
+
+    kart_commands
 }
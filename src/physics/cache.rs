@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy_rapier3d::{
+    prelude::Collider,
+    rapier::{
+        math::Point,
+        parry::transformation::vhacd::{VHACDParameters, VHACD},
+    },
+};
+
+use super::collider::ColliderFromMeshError;
+
+/// Directory of the cached convex decompositions, relative to the working
+/// directory
+#[cfg(not(target_arch = "wasm32"))]
+const CACHE_DIR: &str = ".cache/colliders";
+/// Bumped when the decomposition or the file format changes, so stale files
+/// are ignored
+const CACHE_VERSION: u32 = 2;
+
+/// A convex part of a decomposed mesh
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ConvexPart {
+    vertices: Vec<[f32; 3]>,
+    indices: Vec<[u32; 3]>,
+}
+
+/// The cached decomposition of a mesh
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CacheEntry {
+    /// Size of the decomposed mesh, checked on load in case two meshes share
+    /// the same hash
+    vertex_count: usize,
+    triangle_count: usize,
+    parts: Vec<ConvexPart>,
+}
+
+/// Hash of the mesh's content, the file name of its decomposition in the cache
+///
+/// The hash is stable across builds and platforms, unlike the std hashers
+fn mesh_hash(vertices: &[Vec3], indices: &[[u32; 3]]) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+
+    hasher.update(&CACHE_VERSION.to_le_bytes());
+    for vertex in vertices {
+        for coordinate in vertex.to_array() {
+            hasher.update(&coordinate.to_le_bytes());
+        }
+    }
+    for index in indices.iter().flatten() {
+        hasher.update(&index.to_le_bytes());
+    }
+
+    hasher.finalize()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn cache_path(hash: &blake3::Hash) -> std::path::PathBuf {
+    std::path::Path::new(CACHE_DIR).join(format!("{}.ron", hash.to_hex()))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_parts(
+    hash: &blake3::Hash,
+    vertex_count: usize,
+    triangle_count: usize,
+) -> Option<Vec<ConvexPart>> {
+    let path = cache_path(hash);
+    let content = std::fs::read(&path).ok()?;
+
+    match ron::de::from_bytes::<CacheEntry>(&content) {
+        Ok(entry)
+            if entry.vertex_count == vertex_count && entry.triangle_count == triangle_count =>
+        {
+            Some(entry.parts)
+        }
+        Ok(entry) => {
+            tracing::warn!(
+                "Ignoring the collider cache {path:?}, made for a mesh of {} vertices and {} \
+                triangles instead of {vertex_count} and {triangle_count}",
+                entry.vertex_count,
+                entry.triangle_count
+            );
+            None
+        }
+        Err(err) => {
+            tracing::warn!("Ignoring the invalid collider cache {path:?}: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn store_parts(hash: &blake3::Hash, entry: &CacheEntry) {
+    let path = cache_path(hash);
+
+    let result = ron::to_string(entry)
+        .map_err(|err| err.to_string())
+        .and_then(|content| {
+            std::fs::create_dir_all(CACHE_DIR).map_err(|err| err.to_string())?;
+
+            // Written aside then moved, so another task never reads half a file
+            let temporary_path = path.with_extension("ron.tmp");
+            std::fs::write(&temporary_path, content).map_err(|err| err.to_string())?;
+            std::fs::rename(&temporary_path, &path).map_err(|err| err.to_string())
+        });
+
+    if let Err(err) = result {
+        tracing::warn!("Failed to cache the collider in {path:?}: {err}");
+    }
+}
+
+// There is no file system to cache to on the web
+#[cfg(target_arch = "wasm32")]
+fn load_parts(
+    _hash: &blake3::Hash,
+    _vertex_count: usize,
+    _triangle_count: usize,
+) -> Option<Vec<ConvexPart>> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn store_parts(_hash: &blake3::Hash, _entry: &CacheEntry) {}
+
+/// Same as [`Collider::convex_decomposition`], with the convex parts cached on
+/// disk so a mesh is only decomposed once
+///
+/// Meant to run on the [`bevy::tasks::AsyncComputeTaskPool`], it can take
+/// seconds for a detailed mesh. Fails when no part has a volume, e.g. for a
+/// flat mesh
+pub(super) fn convex_decomposition(
+    vertices: &[Vec3],
+    indices: &[[u32; 3]],
+) -> Result<Collider, ColliderFromMeshError> {
+    let hash = mesh_hash(vertices, indices);
+
+    let parts = load_parts(&hash, vertices.len(), indices.len()).unwrap_or_else(|| {
+        let points: Vec<Point<f32>> = vertices.iter().map(|vertex| (*vertex).into()).collect();
+        let decomposition = VHACD::decompose(&VHACDParameters::default(), &points, indices, true);
+
+        let parts: Vec<ConvexPart> = decomposition
+            .compute_exact_convex_hulls(&points, indices)
+            .into_iter()
+            .map(|(vertices, indices)| ConvexPart {
+                vertices: vertices.iter().map(|point| point.coords.into()).collect(),
+                indices,
+            })
+            .collect();
+
+        let entry = CacheEntry {
+            vertex_count: vertices.len(),
+            triangle_count: indices.len(),
+            parts,
+        };
+        store_parts(&hash, &entry);
+        entry.parts
+    });
+
+    let shapes: Vec<_> = parts
+        .into_iter()
+        .filter_map(|part| {
+            let vertices = part.vertices.into_iter().map(Vec3::from).collect();
+            Collider::convex_mesh(vertices, &part.indices)
+        })
+        .map(|convex| (Vec3::ZERO, Quat::IDENTITY, convex))
+        .collect();
+
+    // A compound needs at least one shape
+    if shapes.is_empty() {
+        return Err(ColliderFromMeshError::DegenerateShape);
+    }
+
+    Ok(Collider::compound(shapes))
+}
@@ -1,18 +1,58 @@
 use bevy::{
+    math::{Affine3A, Vec3Swizzles},
     prelude::*,
     render::mesh::{Indices, VertexAttributeValues},
 };
 use bevy_rapier3d::prelude::Collider;
 
+use super::ColliderShape;
+
+/// Why no collider could be made for a [`super::MeshCollider`] node, attached
+/// to the node when it happens
 #[derive(Debug, Component)]
-pub(super) enum ColliderFromMeshError {
+pub(crate) enum ColliderFromMeshError {
+    /// The node has no child, so no mesh
+    MissingChildren,
+    /// None of the node's children has a mesh
+    MissingMesh,
+    /// The mesh asset is not loaded, or was removed
+    MeshNotLoaded,
     MissingPositions,
     MissingIndices,
     InvalidPositionsType(&'static str),
+    /// The vertices don't enclose any volume, e.g. a flat mesh as a convex hull
+    DegenerateShape,
+}
+
+impl std::fmt::Display for ColliderFromMeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColliderFromMeshError::MissingChildren => write!(f, "the node has no children"),
+            ColliderFromMeshError::MissingMesh => write!(f, "the node has no mesh"),
+            ColliderFromMeshError::MeshNotLoaded => write!(f, "the mesh is not loaded"),
+            ColliderFromMeshError::MissingPositions => {
+                write!(f, "the mesh has no vertex positions")
+            }
+            ColliderFromMeshError::MissingIndices => write!(f, "the mesh has no indices"),
+            ColliderFromMeshError::InvalidPositionsType(positions_type) => {
+                write!(
+                    f,
+                    "the mesh's positions are {positions_type}, not Float32x3"
+                )
+            }
+            ColliderFromMeshError::DegenerateShape => write!(f, "the mesh has no volume"),
+        }
+    }
 }
 
+impl std::error::Error for ColliderFromMeshError {}
+
+/// Vertices and triangles of a mesh, as expected by Rapier's mesh colliders
+pub(crate) type MeshTriangles = (Vec<Vec3>, Vec<[u32; 3]>);
+
+/// Vertices and triangles of a mesh, see [`MeshTriangles`]
 // From https://github.com/Defernus/bevy_gltf_collider/blob/a8ce443/src/mesh_collider.rs#L24-L60
-pub(super) fn mesh_to_collider(mesh: Mesh) -> Result<Collider, ColliderFromMeshError> {
+pub(crate) fn mesh_triangles(mesh: &Mesh) -> Result<MeshTriangles, ColliderFromMeshError> {
     let positions = mesh
         .attribute(Mesh::ATTRIBUTE_POSITION)
         .map_or(Err(ColliderFromMeshError::MissingPositions), Ok)?;
@@ -38,5 +78,299 @@ pub(super) fn mesh_to_collider(mesh: Mesh) -> Result<Collider, ColliderFromMeshE
     let triple_indices: Vec<[u32; 3]> = indices.chunks(3).map(|v| [v[0], v[1], v[2]]).collect();
     let vertices: Vec<Vec3> = positions.iter().map(|v| Vec3::from_array(*v)).collect();
 
-    Ok(Collider::convex_decomposition(&vertices, &triple_indices))
+    Ok((vertices, triple_indices))
+}
+
+/// Moves the triangles of a mesh by `transform`
+///
+/// The vertices are baked so any transform works, even a non-uniform scale
+/// that Rapier can't apply to every collider shape
+pub(crate) fn transform_triangles(
+    transform: Affine3A,
+    vertices: &mut [Vec3],
+    indices: &mut [[u32; 3]],
+) {
+    for vertex in vertices.iter_mut() {
+        *vertex = transform.transform_point3(*vertex);
+    }
+
+    // A mirroring transform flips the triangles inside out
+    if transform.matrix3.determinant() < 0f32 {
+        for triangle in indices.iter_mut() {
+            triangle.swap(1, 2);
+        }
+    }
+}
+
+/// Most heights along each axis of a [`ColliderShape::HeightField`]
+const MAX_HEIGHTFIELD_RESOLUTION: usize = 256;
+
+/// Collider of the given `shape` for the triangles of a mesh, see [`mesh_triangles`]
+///
+/// Can be slow, see [`ColliderShape::ConvexDecomposition`], so it is meant to
+/// run on the [`bevy::tasks::AsyncComputeTaskPool`]
+pub(super) fn mesh_to_collider(
+    vertices: Vec<Vec3>,
+    triple_indices: Vec<[u32; 3]>,
+    shape: ColliderShape,
+) -> Result<Collider, ColliderFromMeshError> {
+    if vertices.is_empty() {
+        return Err(ColliderFromMeshError::DegenerateShape);
+    }
+
+    let min = vertices
+        .iter()
+        .fold(Vec3::MAX, |min, vertex| min.min(*vertex));
+    let max = vertices
+        .iter()
+        .fold(Vec3::MIN, |max, vertex| max.max(*vertex));
+    let center = (min + max) / 2f32;
+    let half_extents = (max - min) / 2f32;
+
+    // Primitive shapes are centered on the origin, they are moved to the
+    // center of the mesh's bounding box
+    let centered =
+        |collider: Collider| Collider::compound(vec![(center, Quat::IDENTITY, collider)]);
+
+    let collider = match shape {
+        ColliderShape::TriMesh => Collider::trimesh(vertices, triple_indices),
+        ColliderShape::ConvexHull => {
+            Collider::convex_hull(&vertices).ok_or(ColliderFromMeshError::DegenerateShape)?
+        }
+        ColliderShape::ConvexDecomposition => {
+            super::cache::convex_decomposition(&vertices, &triple_indices)?
+        }
+        ColliderShape::Cuboid => centered(Collider::cuboid(
+            half_extents.x,
+            half_extents.y,
+            half_extents.z,
+        )),
+        ColliderShape::Ball => centered(Collider::ball(half_extents.max_element())),
+        ColliderShape::Capsule => {
+            // Along the longest axis, as wide as the two others
+            let (axis, half_length, radius) =
+                if half_extents.x >= half_extents.y.max(half_extents.z) {
+                    (Vec3::X, half_extents.x, half_extents.y.max(half_extents.z))
+                } else if half_extents.y >= half_extents.z {
+                    (Vec3::Y, half_extents.y, half_extents.x.max(half_extents.z))
+                } else {
+                    (Vec3::Z, half_extents.z, half_extents.x.max(half_extents.y))
+                };
+            let half_segment = axis * (half_length - radius).max(0f32);
+
+            centered(Collider::capsule(-half_segment, half_segment, radius))
+        }
+        ColliderShape::HeightField => {
+            let heightfield = heightfield(&vertices, &triple_indices, min, max)?;
+            Collider::compound(vec![(
+                Vec3::new(center.x, 0f32, center.z),
+                Quat::IDENTITY,
+                heightfield,
+            )])
+        }
+    };
+
+    Ok(collider)
+}
+
+/// Heightfield of the top surface of the triangles, over their bounding box
+/// from `min` to `max` and centered on its middle along X and Z
+///
+/// The resolution follows the vertex count, so a grid mesh gives back about
+/// one height per vertex
+fn heightfield(
+    vertices: &[Vec3],
+    indices: &[[u32; 3]],
+    min: Vec3,
+    max: Vec3,
+) -> Result<Collider, ColliderFromMeshError> {
+    let size = max - min;
+    if size.x <= 0f32 || size.z <= 0f32 {
+        return Err(ColliderFromMeshError::DegenerateShape);
+    }
+
+    let resolution =
+        ((vertices.len() as f32).sqrt().ceil() as usize).clamp(2, MAX_HEIGHTFIELD_RESOLUTION);
+    // Rows go along Z and columns along X, see `parry3d::shape::HeightField`
+    let (num_rows, num_cols) = (resolution, resolution);
+    let cell_size = Vec2::new(
+        size.x / (num_cols - 1) as f32,
+        size.z / (num_rows - 1) as f32,
+    );
+
+    // Holes are filled with the lowest point of the mesh
+    let mut heights: Vec<Option<f32>> = vec![None; num_rows * num_cols];
+
+    for [a, b, c] in indices.iter() {
+        let [a, b, c] = [a, b, c].map(|index| vertices[*index as usize]);
+        let (a_xz, b_xz, c_xz) = (a.xz() - min.xz(), b.xz() - min.xz(), c.xz() - min.xz());
+
+        let area = (b_xz - a_xz).perp_dot(c_xz - a_xz);
+        // Vertical triangles are not part of the surface
+        if area.abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let triangle_min = a_xz.min(b_xz).min(c_xz) / cell_size;
+        let triangle_max = a_xz.max(b_xz).max(c_xz) / cell_size;
+        let columns =
+            (triangle_min.x.ceil() as usize)..=(triangle_max.x.floor() as usize).min(num_cols - 1);
+        let rows =
+            (triangle_min.y.ceil() as usize)..=(triangle_max.y.floor() as usize).min(num_rows - 1);
+
+        for column in columns {
+            for row in rows.clone() {
+                let point = Vec2::new(column as f32, row as f32) * cell_size;
+
+                // Barycentric coordinates of the point in the triangle
+                let weight_b = (point - a_xz).perp_dot(c_xz - a_xz) / area;
+                let weight_c = (b_xz - a_xz).perp_dot(point - a_xz) / area;
+                let weight_a = 1f32 - weight_b - weight_c;
+                if weight_a < -f32::EPSILON || weight_b < -f32::EPSILON || weight_c < -f32::EPSILON
+                {
+                    continue;
+                }
+
+                let height = weight_a * a.y + weight_b * b.y + weight_c * c.y;
+                // Heights are stored in column-major order
+                let cell = &mut heights[row + column * num_rows];
+                *cell = Some(cell.map_or(height, |current| current.max(height)));
+            }
+        }
+    }
+
+    let heights = heights
+        .into_iter()
+        .map(|height| height.unwrap_or(min.y))
+        .collect();
+
+    Ok(Collider::heightfield(
+        heights,
+        num_rows,
+        num_cols,
+        Vec3::new(size.x, 1f32, size.z),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normal(vertices: &[Vec3], [a, b, c]: [u32; 3]) -> Vec3 {
+        let [a, b, c] = [a, b, c].map(|index| vertices[index as usize]);
+        (b - a).cross(c - a).normalize()
+    }
+
+    #[test]
+    fn baked_triangles_fit_the_transformed_mesh() {
+        let mesh = Mesh::from(shape::Box {
+            min_x: -1f32,
+            max_x: 3f32,
+            min_y: 0f32,
+            max_y: 1f32,
+            min_z: 2f32,
+            max_z: 2.5,
+        });
+        let (mut vertices, mut indices) = mesh_triangles(&mesh).unwrap();
+
+        // Mirrored along Y, no rotation so the box stays axis aligned
+        let transform = GlobalTransform::from(
+            Transform::from_xyz(1f32, 2f32, 3f32).with_scale(Vec3::new(2f32, -3f32, 0.5)),
+        );
+        transform_triangles(transform.affine(), &mut vertices, &mut indices);
+
+        let min = vertices
+            .iter()
+            .fold(Vec3::MAX, |min, vertex| min.min(*vertex));
+        let max = vertices
+            .iter()
+            .fold(Vec3::MIN, |max, vertex| max.max(*vertex));
+        let [corner_a, corner_b] = [Vec3::new(-1f32, 0f32, 2f32), Vec3::new(3f32, 1f32, 2.5)]
+            .map(|corner| transform.transform_point(corner));
+
+        assert_eq!(min, corner_a.min(corner_b));
+        assert_eq!(max, corner_a.max(corner_b));
+    }
+
+    #[test]
+    fn mirrored_triangles_stay_outward() {
+        // Facing up
+        let vertices = vec![Vec3::ZERO, Vec3::Z, Vec3::X];
+        let indices = vec![[0, 1, 2]];
+        assert_eq!(normal(&vertices, indices[0]), Vec3::Y);
+
+        for scale in [
+            Vec3::new(-1f32, 1f32, 1f32),
+            Vec3::new(2f32, 1f32, -0.5),
+            Vec3::new(-1f32, 1f32, -1f32),
+        ] {
+            let (mut vertices, mut indices) = (vertices.clone(), indices.clone());
+            transform_triangles(Affine3A::from_scale(scale), &mut vertices, &mut indices);
+            assert_eq!(normal(&vertices, indices[0]), Vec3::Y, "scale {scale}");
+        }
+
+        // Upside down
+        let (mut vertices, mut indices) = (vertices, indices);
+        transform_triangles(
+            Affine3A::from_scale(Vec3::new(1f32, -1f32, 1f32)),
+            &mut vertices,
+            &mut indices,
+        );
+        assert_eq!(normal(&vertices, indices[0]), Vec3::NEG_Y);
+    }
+
+    #[test]
+    fn heightfield_follows_the_surface() {
+        // A 3x3 grid over [0, 2] along X and Z, rising faster along Z
+        let height = |x: f32, z: f32| x + 2f32 * z;
+        let vertices: Vec<Vec3> = (0..9)
+            .map(|index| {
+                let (x, z) = ((index % 3) as f32, (index / 3) as f32);
+                Vec3::new(x, height(x, z), z)
+            })
+            .collect();
+        let indices: Vec<[u32; 3]> = [0, 1, 3, 4]
+            .into_iter()
+            .flat_map(|corner| {
+                [
+                    [corner, corner + 3, corner + 1],
+                    [corner + 1, corner + 3, corner + 4],
+                ]
+            })
+            .collect();
+        let min = Vec3::new(0f32, 0f32, 0f32);
+        let max = Vec3::new(2f32, 6f32, 2f32);
+
+        let collider = heightfield(&vertices, &indices, min, max).unwrap();
+        let heightfield = collider.raw.as_heightfield().unwrap();
+        // One height per vertex, parry counts the cells between them
+        assert_eq!((heightfield.nrows(), heightfield.ncols()), (2, 2));
+
+        // The heightfield is centered on the middle of the grid
+        for triangle in heightfield.triangles() {
+            for point in [triangle.a, triangle.b, triangle.c] {
+                let expected = height(point.x + 1f32, point.z + 1f32);
+                assert!(
+                    (point.y - expected).abs() < 1e-5,
+                    "height {} at {point}, expected {expected}",
+                    point.y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flat_heightfield_is_degenerate() {
+        let vertices = vec![Vec3::ZERO, Vec3::Y, Vec3::new(1f32, 0f32, 0f32)];
+        let result = heightfield(
+            &vertices,
+            &[[0, 1, 2]],
+            Vec3::ZERO,
+            Vec3::new(1f32, 1f32, 0f32),
+        );
+        assert!(matches!(
+            result,
+            Err(ColliderFromMeshError::DegenerateShape)
+        ));
+    }
 }
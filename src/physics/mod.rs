@@ -1,106 +1,479 @@
-use std::sync::atomic::AtomicUsize;
+use bevy::{
+    ecs::query::Has,
+    math::Affine3A,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use bevy_rapier3d::{
+    plugin::systems::{apply_rigid_body_user_changes, step_simulation},
+    prelude::*,
+};
+use futures_lite::future;
 
-use bevy::prelude::*;
-use bevy_rapier3d::prelude::*;
+use crate::{
+    physics::collider::{
+        mesh_to_collider, mesh_triangles, transform_triangles, ColliderFromMeshError, MeshTriangles,
+    },
+    scene::{SceneReady, SceneReadySet},
+};
 
-use crate::{assets::AssetLoadingState, physics::collider::mesh_to_collider};
+mod cache;
+pub mod collider;
 
-mod collider;
+/// Shape of the collider generated from a mesh, from the most to the least
+/// faithful to the mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, Default)]
+pub enum ColliderShape {
+    /// The triangles of the mesh, only meant for static objects
+    TriMesh,
+    /// Smallest convex shape around the mesh
+    ConvexHull,
+    /// Several convex hulls following the concave parts of the mesh, slow to
+    /// build
+    #[default]
+    ConvexDecomposition,
+    /// Box fitted to the mesh's bounding box
+    Cuboid,
+    /// Sphere around the mesh's bounding box
+    Ball,
+    /// Capsule along the longest axis of the mesh's bounding box
+    Capsule,
+    /// Top surface of the mesh seen from above, for terrains
+    HeightField,
+}
 
+/// Generates a collider from the meshes of a glTF node
+///
+/// Set from the node's custom properties, e.g. `MeshCollider: (shape: TriMesh)`,
+/// an empty value gives a [`ColliderShape::ConvexDecomposition`]
 #[derive(Debug, Component, Reflect, Default)]
 #[reflect(Component)]
-pub struct MeshCollider;
+pub struct MeshCollider {
+    pub shape: ColliderShape,
+}
 
-pub struct GltfColliderPlugin;
+/// Keeps the [`MeshCollider`] nodes of the entity's scene from getting a
+/// collider, e.g. for the karts whose chassis is their only collider
+#[derive(Debug, Component)]
+pub struct WithoutMeshColliders;
 
-#[derive(Debug, Default, Hash, PartialEq, Eq, Clone)]
-#[derive(States)]
-enum ColliderState {
-    #[default]
-    Waiting,
-    Done,
-}
+/// Systems driving the rigid bodies on the [`FixedUpdate`] steps, e.g. the
+/// karts' engine, run before each physics step once Rapier propagated the
+/// transforms of the previous one
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct PhysicsDriveSet;
+
+/// Rapier's simulation, stepped on the [`FixedUpdate`] steps rather than once
+/// per frame, so a race goes the same whatever the frame rate
+///
+/// The rigid bodies are driven in the [`PhysicsDriveSet`]
+pub struct FixedStepPhysicsPlugin;
+
+impl Plugin for FixedStepPhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        type Rapier = RapierPhysicsPlugin<NoUserData>;
 
-const MAX_TICKS: usize = 200;
-/// Only allow `MAX_TICKS` ticks to process colliders, after that we can assume
-/// there was no colliders to process
-static CONVERT_COLLIDERS_TICK_COUNT: AtomicUsize = AtomicUsize::new(0);
-
-/// We don't want to try to process colliders for ever, so we bound this
-/// to `MAX_TICKS` ticks and after AssetLoadingState::Done is reached
-fn valid_state(
-    asset_loading_state: Res<State<AssetLoadingState>>,
-    processing_state: Res<State<ColliderState>>,
-) -> bool {
-    use std::sync::atomic::Ordering;
-    let tick_count = CONVERT_COLLIDERS_TICK_COUNT.fetch_add(1, Ordering::Relaxed);
-
-    tick_count < MAX_TICKS
-        && in_state(AssetLoadingState::Done).run((), (asset_loading_state,))
-        && !in_state(ColliderState::Done).run((), (processing_state,))
+        app.add_plugins(Rapier::default().with_default_system_setup(false));
+
+        let period = app.world.resource::<FixedTime>().period;
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: period.as_secs_f32(),
+                substeps: 1,
+            },
+            ..default()
+        });
+
+        app.configure_sets(
+            FixedUpdate,
+            (
+                PhysicsSet::SyncBackend,
+                PhysicsSet::SyncBackendFlush,
+                PhysicsSet::StepSimulation,
+                PhysicsSet::Writeback,
+            )
+                .chain(),
+        );
+        app.configure_set(
+            FixedUpdate,
+            PhysicsDriveSet
+                .in_set(PhysicsSet::SyncBackend)
+                .after(RapierTransformPropagateSet)
+                .before(apply_rigid_body_user_changes),
+        );
+        app.add_systems(
+            FixedUpdate,
+            (
+                Rapier::get_systems(PhysicsSet::SyncBackend).in_set(PhysicsSet::SyncBackend),
+                Rapier::get_systems(PhysicsSet::SyncBackendFlush)
+                    .in_set(PhysicsSet::SyncBackendFlush),
+                step_simulation::<NoUserData>.in_set(PhysicsSet::StepSimulation),
+                Rapier::get_systems(PhysicsSet::Writeback).in_set(PhysicsSet::Writeback),
+            ),
+        );
+
+        // Rapier's events are kept for two frames like Bevy's, rather than two
+        // steps, so the systems of `Update` see those of every step
+        app.add_systems(
+            First,
+            (
+                Events::<CollisionEvent>::update_system,
+                Events::<ContactForceEvent>::update_system,
+            ),
+        );
+    }
 }
 
+pub struct GltfColliderPlugin;
+
 impl Plugin for GltfColliderPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<MeshCollider>();
-        app.add_state::<ColliderState>();
+        app.register_type::<ColliderShape>();
+
+        app.add_systems(Update, find_all_colliders.in_set(SceneReadySet));
+        app.add_systems(Update, insert_colliders);
+    }
+}
+
+/// Marks the colliders generated for a scene by [`find_all_colliders`]
+#[derive(Debug, Component)]
+struct SceneCollider;
+
+/// Colliders of a [`MeshCollider`] node being made in the background, on an
+/// entity that becomes the colliders once they are done, see [`insert_colliders`]
+#[derive(Debug, Component)]
+struct PendingColliders {
+    node: Entity,
+    /// The node and its scene, for the logs
+    description: String,
+    task: Task<Result<Vec<Collider>, ColliderFromMeshError>>,
+}
+
+fn report_collider_error(
+    cmds: &mut Commands,
+    node: Entity,
+    description: &str,
+    err: ColliderFromMeshError,
+) {
+    tracing::error!("No collider for {description}: {err}");
+
+    // The scene may have been despawned while the collider was being made
+    if let Some(mut node) = cmds.get_entity(node) {
+        node.insert(err);
+    }
+}
+
+/// Transform of `entity` in the space of its ancestor `root`
+///
+/// Computed from the local [`Transform`]s so it is right even before the
+/// [`GlobalTransform`]s of a freshly spawned scene are propagated, and it keeps
+/// non-uniform scales that a [`Transform`] can't always represent
+pub(crate) fn transform_relative_to(
+    entity: Entity,
+    root: Entity,
+    transforms: &Query<&Transform>,
+    parents: &Query<&Parent>,
+) -> Affine3A {
+    let mut affine = Affine3A::IDENTITY;
+    let mut current = entity;
+
+    while current != root {
+        if let Ok(transform) = transforms.get(current) {
+            affine = transform.compute_affine() * affine;
+        }
+        match parents.get(current) {
+            Ok(parent) => current = parent.get(),
+            // `entity` is not a descendant of `root`
+            Err(_) => break,
+        }
+    }
+
+    affine
+}
+
+/// Triangles of the meshes under a [`MeshCollider`] node, in the space of `root`
+fn node_triangles(
+    root: Entity,
+    children: Option<&Children>,
+    meshes: &Assets<Mesh>,
+    child_mesh_query: &Query<&Handle<Mesh>>,
+    parent_query: &Query<&Parent>,
+    transform_query: &Query<&Transform>,
+) -> Result<Vec<MeshTriangles>, ColliderFromMeshError> {
+    let children = children.ok_or(ColliderFromMeshError::MissingChildren)?;
+
+    // A glTF node has one child per primitive of its mesh
+    let mut triangles = Vec::new();
+    for &child in children.iter() {
+        let Ok(mesh_handle) = child_mesh_query.get(child) else {
+            continue;
+        };
+
+        let mesh = meshes
+            .get(mesh_handle)
+            .ok_or(ColliderFromMeshError::MeshNotLoaded)?;
+        let (mut vertices, mut indices) = mesh_triangles(mesh)?;
+        let transform = transform_relative_to(child, root, transform_query, parent_query);
+        transform_triangles(transform, &mut vertices, &mut indices);
 
-        app.add_systems(Update, find_all_colliders.run_if(valid_state));
+        triangles.push((vertices, indices));
     }
+
+    if triangles.is_empty() {
+        return Err(ColliderFromMeshError::MissingMesh);
+    }
+
+    Ok(triangles)
 }
 
+/// Starts making a collider for every mesh under a [`MeshCollider`] node, once
+/// the scene is spawned
+///
+/// The colliders are made in the background, the meshes stay untouched, and
+/// attached to the scene's root entity by [`insert_colliders`]. The root gets a
+/// `RigidBody` if it has none. The whole hierarchy of transforms between the
+/// root and the mesh is baked into the colliders' vertices
+///
+/// A node whose collider can't be made is left without one, and gets the
+/// [`ColliderFromMeshError`] as a component
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn find_all_colliders(
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut scene_ready: EventReader<SceneReady>,
+    scene_spawner: Res<SceneSpawner>,
+    asset_server: Res<AssetServer>,
+    meshes: Res<Assets<Mesh>>,
     mut cmds: Commands,
-    mut query: Query<(Entity, &Transform, Option<&Children>), With<MeshCollider>>,
+    query: Query<(&MeshCollider, Option<&Children>, Option<&Name>)>,
     child_mesh_query: Query<&Handle<Mesh>>,
     parent_query: Query<&Parent>,
-    mut state: ResMut<NextState<ColliderState>>,
+    transform_query: Query<&Transform>,
+    root_query: Query<
+        (Option<&Children>, Option<&Handle<Scene>>, Has<RigidBody>),
+        Without<WithoutMeshColliders>,
+    >,
+    scene_collider_query: Query<Entity, With<SceneCollider>>,
 ) {
-    let mut found_collider = false;
-    for (entity, entity_transform, children) in query.iter_mut() {
-        let children = children.expect("MeshCollider component without children");
-
-        found_collider = true;
-        let mut found_mesh = false;
-        for child in children.iter() {
-            if let Ok(mesh_handle) = child_mesh_query.get(*child) {
-                found_mesh = true;
-
-                let mesh = meshes.remove(mesh_handle).expect("Mesh not found");
-                let collider = mesh_to_collider(mesh).unwrap();
-
-                // We find the top level entity to add the RigidBody component
-                let mut top_level_entity = entity;
-                while let Ok(parent) = parent_query.get(top_level_entity) {
-                    top_level_entity = parent.get();
+    for SceneReady { root, instance } in scene_ready.iter() {
+        let Ok((root_children, scene, has_rigid_body)) = root_query.get(*root) else {
+            // Despawned in the meantime, or not meant to collide
+            continue;
+        };
+
+        // The colliders of the previous scene were not part of the scene, so
+        // they were not despawned with it. Pending ones are cancelled
+        if let Some(root_children) = root_children {
+            for collider in scene_collider_query.iter_many(root_children) {
+                cmds.entity(collider).despawn_recursive();
+            }
+        }
+
+        let scene_path = scene.and_then(|scene| asset_server.get_handle_path(scene));
+
+        let mut found_collider = false;
+        for entity in scene_spawner.iter_instance_entities(*instance) {
+            let Ok((mesh_collider, children, name)) = query.get(entity) else {
+                continue;
+            };
+
+            let description = format!(
+                "node {:?} ({entity:?}) of scene {:?}",
+                name.map_or("unnamed", Name::as_str),
+                scene_path.as_ref().map(|path| path.path())
+            );
+
+            let triangles = node_triangles(
+                *root,
+                children,
+                &meshes,
+                &child_mesh_query,
+                &parent_query,
+                &transform_query,
+            );
+            let triangles = match triangles {
+                Ok(triangles) => triangles,
+                Err(err) => {
+                    report_collider_error(&mut cmds, entity, &description, err);
+                    continue;
                 }
+            };
 
-                // Scale is not applied
-                // let collider_transform = Transform::default();
+            found_collider = true;
 
-                // Seems to apply rotation twice ?
-                let collider_transform = *entity_transform;
+            let shape = mesh_collider.shape;
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                triangles
+                    .into_iter()
+                    .map(|(vertices, indices)| mesh_to_collider(vertices, indices, shape))
+                    .collect()
+            });
 
-                // Same as Transform::default() (???)
-                // let collider_transform = Transform::from_scale(entity_transform.scale);
-                dbg!(collider_transform);
+            cmds.entity(*root).with_children(|parent| {
+                parent.spawn((
+                    SceneCollider,
+                    PendingColliders {
+                        node: entity,
+                        description,
+                        task,
+                    },
+                ));
+            });
+        }
 
-                cmds.entity(entity).with_children(|parent| {
-                    parent.spawn((collider, collider_transform));
-                });
-                // Only the top level entity has the RigidBody component
-                cmds.entity(top_level_entity)
-                    .insert((RigidBody::Dynamic, Ccd::enabled()));
+        // Only the root entity has the RigidBody component
+        if found_collider && !has_rigid_body {
+            cmds.entity(*root)
+                .insert((RigidBody::Dynamic, Ccd::enabled()));
+        }
+    }
+}
+
+/// Attaches the colliders started by [`find_all_colliders`] once they are made
+fn insert_colliders(
+    mut cmds: Commands,
+    mut query: Query<(Entity, &Parent, &mut PendingColliders)>,
+) {
+    for (entity, parent, mut pending) in query.iter_mut() {
+        let Some(colliders) = future::block_on(future::poll_once(&mut pending.task)) else {
+            continue;
+        };
 
-                break;
+        cmds.entity(entity).despawn_recursive();
+
+        match colliders {
+            Ok(colliders) => {
+                // The transform is baked in the collider's vertices
+                cmds.entity(parent.get()).with_children(|parent| {
+                    for collider in colliders {
+                        parent.spawn((SceneCollider, collider, TransformBundle::IDENTITY));
+                    }
+                });
             }
+            Err(err) => report_collider_error(&mut cmds, pending.node, &pending.description, err),
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::system::SystemState,
+        transform::systems::{propagate_transforms, sync_simple_transforms},
+    };
+
+    use super::*;
+
+    /// Smallest box around the `points`, as its min and max corners
+    fn aabb(points: impl IntoIterator<Item = Vec3>) -> (Vec3, Vec3) {
+        points
+            .into_iter()
+            .fold((Vec3::MAX, Vec3::MIN), |(min, max), point| {
+                (min.min(point), max.max(point))
+            })
+    }
+
+    #[test]
+    fn baked_triangles_follow_the_hierarchy() {
+        let mut world = World::new();
 
-        assert!(found_mesh, "MeshCollider component without mesh");
+        let root = world
+            .spawn(TransformBundle::from_transform(
+                Transform::from_xyz(5f32, -2f32, 1f32).with_rotation(Quat::from_rotation_y(0.3)),
+            ))
+            .id();
+        // Non-uniform and mirrored scales, under rotations so they shear the child
+        let node = world
+            .spawn(TransformBundle::from_transform(
+                Transform::from_xyz(1f32, 2f32, 3f32)
+                    .with_rotation(Quat::from_rotation_x(0.7))
+                    .with_scale(Vec3::new(2f32, -1f32, 0.5)),
+            ))
+            .id();
+        let child = world
+            .spawn(TransformBundle::from_transform(
+                Transform::from_xyz(-1f32, 0f32, 4f32)
+                    .with_rotation(Quat::from_rotation_z(-1.2))
+                    .with_scale(Vec3::new(-1f32, 3f32, 1f32)),
+            ))
+            .id();
+        world.entity_mut(root).push_children(&[node]);
+        world.entity_mut(node).push_children(&[child]);
+
+        let mut schedule = Schedule::new();
+        schedule.add_systems((sync_simple_transforms, propagate_transforms));
+        schedule.run(&mut world);
+
+        let mesh = Mesh::from(shape::Box {
+            min_x: 0.5,
+            max_x: 1.5,
+            min_y: -1f32,
+            max_y: 2f32,
+            min_z: 1f32,
+            max_z: 2f32,
+        });
+        let (mut vertices, mut indices) = mesh_triangles(&mesh).unwrap();
+        let (mesh_min, mesh_max) = aabb(vertices.iter().copied());
+
+        let mut state = SystemState::<(Query<&Transform>, Query<&Parent>)>::new(&mut world);
+        let (transforms, parents) = state.get(&world);
+        let transform = transform_relative_to(child, root, &transforms, &parents);
+        transform_triangles(transform, &mut vertices, &mut indices);
+
+        // The baked triangles are in the space of the root
+        let root_transform = world.get::<GlobalTransform>(root).unwrap().affine();
+        let (min, max) = aabb(
+            vertices
+                .iter()
+                .map(|vertex| root_transform.transform_point3(*vertex)),
+        );
+
+        let child_transform = world.get::<GlobalTransform>(child).unwrap().affine();
+        let corners = (0..8).map(|corner| {
+            Vec3::select(
+                BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                mesh_max,
+                mesh_min,
+            )
+        });
+        let (expected_min, expected_max) =
+            aabb(corners.map(|corner| child_transform.transform_point3(corner)));
+
+        assert!(
+            min.abs_diff_eq(expected_min, 1e-4),
+            "{min} != {expected_min}"
+        );
+        assert!(
+            max.abs_diff_eq(expected_max, 1e-4),
+            "{max} != {expected_max}"
+        );
     }
 
-    if found_collider {
-        state.set(ColliderState::Done);
+    #[test]
+    fn root_space_ignores_the_root_transform() {
+        let mut world = World::new();
+
+        let root = world
+            .spawn(TransformBundle::from_transform(Transform::from_xyz(
+                10f32, 0f32, 0f32,
+            )))
+            .id();
+        let child = world
+            .spawn(TransformBundle::from_transform(
+                Transform::from_xyz(0f32, 1f32, 0f32).with_scale(Vec3::splat(2f32)),
+            ))
+            .id();
+        world.entity_mut(root).push_children(&[child]);
+
+        let mut state = SystemState::<(Query<&Transform>, Query<&Parent>)>::new(&mut world);
+        let (transforms, parents) = state.get(&world);
+
+        assert_eq!(
+            transform_relative_to(root, root, &transforms, &parents),
+            Affine3A::IDENTITY
+        );
+        assert_eq!(
+            transform_relative_to(child, root, &transforms, &parents),
+            Affine3A::from_scale_rotation_translation(Vec3::splat(2f32), Quat::IDENTITY, Vec3::Y)
+        );
     }
 }
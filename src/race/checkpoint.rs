@@ -0,0 +1,304 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::wrong_way::WrongWayChanged;
+use crate::{
+    kart::Chassis,
+    scene::SceneReady,
+    track::{registry::Track, TrackScene},
+};
+
+/// Speed through a volume under which a kart is not going through it, in
+/// meters per second, so karts pushed against it don't cross it
+const CROSSING_SPEED: f32 = 0.1f32;
+
+/// A checkpoint of the track, to pass in order of `index` from 0 on every lap
+///
+/// Set from the node's custom properties, e.g. `Checkpoint: (index: 0)`. The
+/// volume is the node's cube from -1 to 1 (like Blender's default cube or a
+/// cube empty) scaled by the node, and karts go through it along its local +Z
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Checkpoint {
+    pub index: u32,
+}
+
+/// The start and finish line of the track, a volume like the [`Checkpoint`]s
+///
+/// Set from the node's custom properties, e.g. `FinishLine: ""`
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct FinishLine;
+
+/// The [`Checkpoint`]s of the track, read once its scene is spawned
+#[derive(Debug, Resource, Default)]
+pub struct TrackCheckpoints {
+    /// `None` until the track's scene is spawned, and for a track whose
+    /// checkpoints are invalid
+    count: Option<u32>,
+}
+
+impl TrackCheckpoints {
+    /// Number of checkpoints of the track, `None` when laps can't be counted
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+}
+
+/// Number of checkpoints with the given indices, which must go from 0 without
+/// gaps or repeats
+///
+/// A track needs at least one checkpoint, or any crossing of the finish line
+/// would complete a lap
+fn checkpoint_count(mut indices: Vec<u32>) -> Result<u32, String> {
+    if indices.is_empty() {
+        return Err("the track has no checkpoint".to_string());
+    }
+
+    indices.sort_unstable();
+    for (expected, index) in indices.iter().enumerate() {
+        if *index != expected as u32 {
+            return Err(format!(
+                "the track's checkpoints skip or repeat index {expected}, got {index}"
+            ));
+        }
+    }
+
+    Ok(indices.len() as u32)
+}
+
+/// Reads the [`Checkpoint`]s of the track once its scene is spawned
+pub fn collect_checkpoints(
+    mut scene_ready: EventReader<SceneReady>,
+    scene_spawner: Res<SceneSpawner>,
+    mut track_checkpoints: ResMut<TrackCheckpoints>,
+    track_scenes: Query<(), With<TrackScene>>,
+    checkpoints: Query<&Checkpoint>,
+) {
+    for SceneReady { root, instance } in scene_ready.iter() {
+        if !track_scenes.contains(*root) {
+            continue;
+        }
+
+        let indices = checkpoints
+            .iter_many(scene_spawner.iter_instance_entities(*instance))
+            .map(|checkpoint| checkpoint.index)
+            .collect();
+
+        track_checkpoints.count = match checkpoint_count(indices) {
+            Ok(count) => {
+                tracing::info!("Found {count} checkpoints in the track");
+                Some(count)
+            }
+            Err(err) => {
+                tracing::error!("Laps can't be counted: {err}");
+                None
+            }
+        };
+    }
+}
+
+/// Lap counting of a kart
+#[derive(Debug, Component, Default)]
+pub struct LapProgress {
+    /// Lap the kart is driving, from 1, 0 until the kart crosses the start line
+    pub lap: u32,
+    /// Index of the next [`Checkpoint`] to pass, the number of checkpoints when
+    /// they are all passed and the kart heads to the finish line
+    pub next_checkpoint: u32,
+    /// Set once the kart completed the race's laps
+    pub finished: bool,
+}
+
+/// Sent when a kart passes its next [`Checkpoint`]
+#[derive(Debug, Event)]
+pub struct CheckpointPassed {
+    pub kart: Entity,
+    pub index: u32,
+}
+
+/// Sent when a kart crosses the finish line to start a lap
+#[derive(Debug, Event)]
+pub struct LapStarted {
+    pub kart: Entity,
+    /// The started lap, from 1
+    pub lap: u32,
+}
+
+/// Sent when a kart completes a lap
+#[derive(Debug, Event)]
+pub struct LapCompleted {
+    pub kart: Entity,
+    /// The completed lap, from 1
+    pub lap: u32,
+}
+
+/// Sent when a kart completes the last lap of the race
+#[derive(Debug, Event)]
+pub struct RaceFinished {
+    pub kart: Entity,
+}
+
+/// Turns the [`Checkpoint`] and [`FinishLine`] nodes of the track into hidden
+/// sensors
+#[allow(clippy::type_complexity)]
+pub fn init_checkpoints(
+    mut commands: Commands,
+    checkpoints: Query<Entity, Or<(Added<Checkpoint>, Added<FinishLine>)>>,
+) {
+    for entity in checkpoints.iter() {
+        commands.entity(entity).insert((
+            Collider::cuboid(1f32, 1f32, 1f32),
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            Visibility::Hidden,
+        ));
+    }
+}
+
+/// Kart whose [`Chassis`] is `collider`
+///
+/// Only the chassis is looked at, so a kart made of several colliders goes
+/// through a checkpoint once
+fn chassis_kart(
+    collider: Entity,
+    chassis: &Query<&Parent, With<Chassis>>,
+    karts: &Query<(&Velocity, &mut LapProgress)>,
+) -> Option<Entity> {
+    let kart = chassis.get(collider).ok()?.get();
+    karts.contains(kart).then_some(kart)
+}
+
+/// Counts the laps of the karts going through the checkpoints
+///
+/// A checkpoint only counts when it is the next one and it is crossed forward,
+/// so shortcuts are ignored. Going back through the previous checkpoint, or the
+/// finish line, undoes it
+#[allow(clippy::too_many_arguments)]
+pub fn update_lap_progress(
+    track: Res<Track>,
+    track_checkpoints: Res<TrackCheckpoints>,
+    mut collision_events: EventReader<CollisionEvent>,
+    checkpoints: Query<(&Checkpoint, &GlobalTransform)>,
+    finish_lines: Query<&GlobalTransform, With<FinishLine>>,
+    chassis: Query<&Parent, With<Chassis>>,
+    mut karts: Query<(&Velocity, &mut LapProgress)>,
+    mut checkpoint_passed: EventWriter<CheckpointPassed>,
+    mut lap_started: EventWriter<LapStarted>,
+    mut lap_completed: EventWriter<LapCompleted>,
+    mut race_finished: EventWriter<RaceFinished>,
+) {
+    let Some(checkpoint_count) = track_checkpoints.count() else {
+        collision_events.clear();
+        return;
+    };
+
+    for collision_event in collision_events.iter() {
+        let CollisionEvent::Started(first, second, _) = *collision_event else {
+            continue;
+        };
+
+        let Some((kart, volume)) = chassis_kart(first, &chassis, &karts)
+            .map(|kart| (kart, second))
+            .or_else(|| chassis_kart(second, &chassis, &karts).map(|kart| (kart, first)))
+        else {
+            continue;
+        };
+        let Ok((velocity, mut progress)) = karts.get_mut(kart) else {
+            continue;
+        };
+        if progress.finished {
+            continue;
+        }
+
+        // Karts go through the volumes along their local +Z, `back` in Bevy's
+        // terms, as `(is_forward, is_backward)`
+        let crossing = |transform: &GlobalTransform| {
+            let speed = velocity.linvel.dot(transform.back());
+            (speed > CROSSING_SPEED, speed < -CROSSING_SPEED)
+        };
+
+        if let Ok((checkpoint, transform)) = checkpoints.get(volume) {
+            let (is_forward, is_backward) = crossing(transform);
+
+            if is_forward && checkpoint.index == progress.next_checkpoint {
+                progress.next_checkpoint += 1;
+                checkpoint_passed.send(CheckpointPassed {
+                    kart,
+                    index: checkpoint.index,
+                });
+            } else if is_backward && checkpoint.index + 1 == progress.next_checkpoint {
+                progress.next_checkpoint -= 1;
+            }
+        } else if let Ok(transform) = finish_lines.get(volume) {
+            let (is_forward, is_backward) = crossing(transform);
+
+            if is_forward && (progress.lap == 0 || progress.next_checkpoint == checkpoint_count) {
+                if progress.lap > 0 {
+                    lap_completed.send(LapCompleted {
+                        kart,
+                        lap: progress.lap,
+                    });
+                }
+
+                if progress.lap == track.laps {
+                    progress.finished = true;
+                    race_finished.send(RaceFinished { kart });
+                } else {
+                    progress.lap += 1;
+                    progress.next_checkpoint = 0;
+                    lap_started.send(LapStarted {
+                        kart,
+                        lap: progress.lap,
+                    });
+                }
+            } else if is_backward && progress.lap > 0 && progress.next_checkpoint == 0 {
+                progress.lap -= 1;
+                progress.next_checkpoint = checkpoint_count;
+            }
+        }
+    }
+}
+
+pub fn log_race_events(
+    mut checkpoint_passed: EventReader<CheckpointPassed>,
+    mut lap_completed: EventReader<LapCompleted>,
+    mut race_finished: EventReader<RaceFinished>,
+    mut wrong_way_changed: EventReader<WrongWayChanged>,
+) {
+    for CheckpointPassed { kart, index } in checkpoint_passed.iter() {
+        tracing::debug!("Kart {kart:?} passed checkpoint {index}");
+    }
+    for LapCompleted { kart, lap } in lap_completed.iter() {
+        tracing::info!("Kart {kart:?} completed lap {lap}");
+    }
+    for RaceFinished { kart } in race_finished.iter() {
+        tracing::info!("Kart {kart:?} finished the race");
+    }
+    for WrongWayChanged { kart, is_wrong_way } in wrong_way_changed.iter() {
+        if *is_wrong_way {
+            tracing::info!("Kart {kart:?} is going the wrong way");
+        } else {
+            tracing::debug!("Kart {kart:?} is back on the right way");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoints_are_counted_in_any_order() {
+        assert_eq!(checkpoint_count(vec![0]), Ok(1));
+        assert_eq!(checkpoint_count(vec![2, 0, 3, 1]), Ok(4));
+    }
+
+    #[test]
+    fn checkpoints_must_go_from_0_without_gaps() {
+        assert!(checkpoint_count(vec![]).is_err());
+        assert!(checkpoint_count(vec![1, 2]).is_err());
+        assert!(checkpoint_count(vec![0, 2, 3]).is_err());
+        assert!(checkpoint_count(vec![0, 1, 1, 2]).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{timing::RaceTimings, RaceMode};
+use crate::{
+    kart::{
+        registry::{KartId, KartRegistry},
+        stats::KartStats,
+        Kart, KartInput, Player,
+    },
+    physics::WithoutMeshColliders,
+    save::{self, SaveFormat},
+    scene::SceneReady,
+    track::registry::Track,
+};
+
+/// Opacity of the ghost kart
+const GHOST_ALPHA: f32 = 0.4f32;
+
+/// Name of the save of the track's ghost, see [`crate::save`]
+fn save_name(track: &Track) -> String {
+    format!("ghosts/{}", track.id.0)
+}
+
+/// Pose and input of the kart on a [`FixedUpdate`] step, as
+/// `(translation, rotation, input)`
+///
+/// The input is packed in a byte, see [`GhostSample::pack_input`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GhostSample([f32; 3], [f32; 4], u8);
+
+impl GhostSample {
+    const THROTTLE_SHIFT: u8 = 0;
+    const STEERING_SHIFT: u8 = 2;
+    const JUMP: u8 = 1 << 4;
+    const DRIFT: u8 = 1 << 5;
+
+    /// Size in a [`Ghost`]'s save, 7 little endian floats and the input
+    const SIZE: usize = 7 * 4 + 1;
+
+    fn new(transform: &Transform, input: &KartInput) -> Self {
+        Self(
+            transform.translation.to_array(),
+            transform.rotation.to_array(),
+            Self::pack_input(input),
+        )
+    }
+
+    /// Throttle and steering are kept as -1, 0 or 1, like the player's input
+    fn pack_input(input: &KartInput) -> u8 {
+        let pack_axis = |axis: f32| match axis {
+            axis if axis > 0f32 => 1u8,
+            axis if axis < 0f32 => 2u8,
+            _ => 0u8,
+        };
+
+        let mut packed = pack_axis(input.throttle) << Self::THROTTLE_SHIFT
+            | pack_axis(input.steering) << Self::STEERING_SHIFT;
+        if input.jump {
+            packed |= Self::JUMP;
+        }
+        if input.drift {
+            packed |= Self::DRIFT;
+        }
+        packed
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        for value in self.0.iter().chain(&self.1) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.push(self.2);
+    }
+
+    /// Reads a sample written by [`GhostSample::write`], `bytes` is
+    /// [`GhostSample::SIZE`] long
+    fn read(bytes: &[u8]) -> Self {
+        let float = |index: usize| {
+            let start = index * 4;
+            f32::from_le_bytes(bytes[start..start + 4].try_into().unwrap())
+        };
+
+        Self(
+            [float(0), float(1), float(2)],
+            [float(3), float(4), float(5), float(6)],
+            bytes[Self::SIZE - 1],
+        )
+    }
+
+    fn transform(&self) -> Transform {
+        Transform::from_translation(Vec3::from_array(self.0))
+            .with_rotation(Quat::from_array(self.1))
+    }
+
+    fn input(&self) -> KartInput {
+        let unpack_axis = |shift: u8| match (self.2 >> shift) & 0b11 {
+            1 => 1f32,
+            2 => -1f32,
+            _ => 0f32,
+        };
+
+        KartInput {
+            throttle: unpack_axis(Self::THROTTLE_SHIFT),
+            steering: unpack_axis(Self::STEERING_SHIFT),
+            jump: self.2 & Self::JUMP != 0,
+            drift: self.2 & Self::DRIFT != 0,
+            // The ghost follows the recorded transforms, respawns included
+            respawn: false,
+        }
+    }
+}
+
+/// A recorded time trial, one sample per [`FixedUpdate`] step from the start
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Ghost {
+    version: u32,
+    pub kart: KartId,
+    /// Time of the whole race
+    pub time: Duration,
+    /// Saved as bytes, a RON list of samples is about 3 times larger
+    #[serde(
+        serialize_with = "serialize_samples",
+        deserialize_with = "deserialize_samples"
+    )]
+    pub samples: Vec<GhostSample>,
+}
+
+impl SaveFormat for Ghost {
+    const VERSION: u32 = 1;
+}
+
+fn serialize_samples<S: serde::Serializer>(
+    samples: &[GhostSample],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut bytes = Vec::with_capacity(samples.len() * GhostSample::SIZE);
+    for sample in samples {
+        sample.write(&mut bytes);
+    }
+
+    serializer.serialize_bytes(&bytes)
+}
+
+fn deserialize_samples<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<GhostSample>, D::Error> {
+    struct SamplesVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for SamplesVisitor {
+        type Value = Vec<GhostSample>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(formatter, "bytes of {}-byte samples", GhostSample::SIZE)
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+            let chunks = bytes.chunks_exact(GhostSample::SIZE);
+            if !chunks.remainder().is_empty() {
+                return Err(E::invalid_length(bytes.len(), &self));
+            }
+
+            Ok(chunks.map(GhostSample::read).collect())
+        }
+
+        fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+            self.visit_bytes(&bytes)
+        }
+    }
+
+    deserializer.deserialize_byte_buf(SamplesVisitor)
+}
+
+/// Best time trial of the track, raced against as a ghost
+#[derive(Debug, Resource, Default)]
+pub struct BestGhost(pub Option<Ghost>);
+
+/// The player's race being recorded
+#[derive(Debug, Resource, Default)]
+pub struct GhostRecording {
+    samples: Vec<GhostSample>,
+}
+
+/// Kart replaying a [`Ghost`]
+///
+/// Keeps its own samples, so it finishes its race when a new [`BestGhost`] is
+/// set
+#[derive(Debug, Component)]
+pub struct GhostKart {
+    samples: Vec<GhostSample>,
+    /// Index of the next sample
+    next_sample: usize,
+}
+
+pub(super) fn load_best_ghost(track: Res<Track>, mut best_ghost: ResMut<BestGhost>) {
+    best_ghost.0 = save::load(&save_name(&track));
+}
+
+/// Replaces the ghost kart by a new one at the start, with the latest
+/// [`BestGhost`], and starts a new recording
+pub(super) fn spawn_ghost(
+    mut commands: Commands,
+    mode: Res<RaceMode>,
+    best_ghost: Res<BestGhost>,
+    kart_registry: Res<KartRegistry>,
+    kart_stats: Res<Assets<KartStats>>,
+    ghosts: Query<Entity, With<GhostKart>>,
+) {
+    commands.insert_resource(GhostRecording::default());
+
+    for entity in ghosts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let Some(ghost) = best_ghost.0.as_ref() else {
+        return;
+    };
+    if *mode != RaceMode::TimeTrial || ghost.samples.is_empty() {
+        return;
+    }
+
+    let Some(kart_entry) = kart_registry.get(&ghost.kart) else {
+        tracing::warn!(
+            "No ghost, its kart {:?} is not in the manifest",
+            ghost.kart.0
+        );
+        return;
+    };
+    let stats = kart_stats
+        .get(&kart_entry.stats)
+        .expect("Kart stats are loaded with the kart assets");
+
+    // No rigid body or collider, the ghost goes through everything
+    commands.spawn((
+        SceneBundle {
+            scene: kart_entry.scene.clone(),
+            transform: ghost.samples[0].transform(),
+            ..default()
+        },
+        WithoutMeshColliders,
+        // Steers the front wheels like the recorded kart
+        Kart::from_stats(stats),
+        KartInput::default(),
+        GhostKart {
+            samples: ghost.samples.clone(),
+            next_sample: 0,
+        },
+    ));
+}
+
+pub(super) fn record_ghost(
+    mut recording: ResMut<GhostRecording>,
+    players: Query<(&Transform, &KartInput), With<Player>>,
+) {
+    for (transform, input) in players.iter() {
+        recording.samples.push(GhostSample::new(transform, input));
+    }
+}
+
+/// Keeps the player's race as the [`BestGhost`] when it beats it
+pub(super) fn save_ghost(
+    track: Res<Track>,
+    mut recording: ResMut<GhostRecording>,
+    mut best_ghost: ResMut<BestGhost>,
+    timings: Res<RaceTimings>,
+    players: Query<(Entity, &KartId), With<Player>>,
+) {
+    let Ok((kart, kart_id)) = players.get_single() else {
+        return;
+    };
+    let Some(time) = timings.get(kart).and_then(|timing| timing.total) else {
+        return;
+    };
+    if best_ghost
+        .0
+        .as_ref()
+        .is_some_and(|ghost| ghost.time <= time)
+    {
+        return;
+    }
+
+    let ghost = Ghost {
+        version: Ghost::VERSION,
+        kart: kart_id.clone(),
+        time,
+        samples: std::mem::take(&mut recording.samples),
+    };
+    save::store(&save_name(&track), &ghost);
+    best_ghost.0 = Some(ghost);
+}
+
+pub(super) fn play_ghost(
+    mut ghosts: Query<(
+        &mut GhostKart,
+        &mut Transform,
+        &mut KartInput,
+        &mut Visibility,
+    )>,
+) {
+    for (mut ghost_kart, mut transform, mut input, mut visibility) in ghosts.iter_mut() {
+        let Some(sample) = ghost_kart.samples.get(ghost_kart.next_sample) else {
+            // The ghost finished its race
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        *transform = sample.transform();
+        *input = sample.input();
+        ghost_kart.next_sample += 1;
+    }
+}
+
+/// Makes the materials of the ghost karts' scenes translucent
+pub(super) fn make_ghost_translucent(
+    mut scene_ready: EventReader<SceneReady>,
+    mut translucent_materials: Local<HashMap<Handle<StandardMaterial>, Handle<StandardMaterial>>>,
+    scene_spawner: Res<SceneSpawner>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    ghosts: Query<(), With<GhostKart>>,
+    mut meshes: Query<&mut Handle<StandardMaterial>>,
+) {
+    for SceneReady { root, instance } in scene_ready.iter() {
+        if !ghosts.contains(*root) {
+            continue;
+        }
+
+        for entity in scene_spawner.iter_instance_entities(*instance) {
+            let Ok(mut material_handle) = meshes.get_mut(entity) else {
+                continue;
+            };
+
+            // The materials are shared with the other karts' scenes
+            let translucent_handle = match translucent_materials.get(&*material_handle) {
+                Some(translucent_handle) => translucent_handle.clone(),
+                None => {
+                    let Some(material) = materials.get(&material_handle) else {
+                        continue;
+                    };
+
+                    let mut translucent = material.clone();
+                    translucent.base_color.set_a(GHOST_ALPHA);
+                    translucent.alpha_mode = AlphaMode::Blend;
+
+                    let translucent_handle = materials.add(translucent);
+                    translucent_materials
+                        .insert(material_handle.clone(), translucent_handle.clone());
+                    translucent_handle
+                }
+            };
+
+            *material_handle = translucent_handle;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(index: f32, input: u8) -> GhostSample {
+        GhostSample(
+            [index, -index * 0.5f32, 1e-3f32],
+            Quat::from_rotation_y(index).to_array(),
+            input,
+        )
+    }
+
+    #[test]
+    fn input_survives_packing() {
+        for throttle in [-1f32, 0f32, 1f32] {
+            for steering in [-1f32, 0f32, 1f32] {
+                for jump in [false, true] {
+                    for drift in [false, true] {
+                        let input = KartInput {
+                            throttle,
+                            steering,
+                            jump,
+                            drift,
+                            respawn: false,
+                        };
+                        let packed = GhostSample::pack_input(&input);
+
+                        assert_eq!(GhostSample([0f32; 3], [0f32; 4], packed).input(), input);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analog_input_is_rounded_to_its_direction() {
+        let input = KartInput {
+            throttle: 0.3f32,
+            steering: -0.8f32,
+            ..default()
+        };
+        let unpacked = GhostSample([0f32; 3], [0f32; 4], GhostSample::pack_input(&input)).input();
+
+        assert_eq!(unpacked.throttle, 1f32);
+        assert_eq!(unpacked.steering, -1f32);
+    }
+
+    #[test]
+    fn samples_are_saved_as_bytes() {
+        let ghost = Ghost {
+            version: Ghost::VERSION,
+            kart: KartId("sedan".to_string()),
+            time: Duration::from_secs(90),
+            samples: (0..100).map(|index| sample(index as f32, index)).collect(),
+        };
+
+        let content = ron::to_string(&ghost).unwrap();
+        let read: Ghost = ron::from_str(&content).unwrap();
+
+        assert_eq!(read.samples, ghost.samples);
+        assert!(content.len() < ghost.samples.len() * GhostSample::SIZE * 2);
+    }
+
+    #[test]
+    fn truncated_samples_are_rejected() {
+        let mut bytes = Vec::new();
+        sample(1f32, 0).write(&mut bytes);
+        sample(2f32, 0).write(&mut bytes);
+
+        let deserializer =
+            |bytes| serde::de::value::BytesDeserializer::<serde::de::value::Error>::new(bytes);
+        assert_eq!(deserialize_samples(deserializer(&bytes)).unwrap().len(), 2);
+        assert!(deserialize_samples(deserializer(&bytes[..bytes.len() - 1])).is_err());
+    }
+}
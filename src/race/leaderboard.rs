@@ -0,0 +1,313 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::{
+    format_race_time,
+    state::{screen_node, text_style},
+    timing::RaceTimings,
+};
+use crate::{
+    kart::{
+        registry::{KartId, KartRegistry},
+        Player,
+    },
+    save::{self, SaveFormat},
+    track::registry::{Track, TrackId},
+};
+
+/// Name of the leaderboard's save, see [`crate::save`]
+const SAVE_NAME: &str = "leaderboard";
+
+/// Best times of a kart on a track
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Record {
+    pub track: TrackId,
+    pub kart: KartId,
+    pub best_lap: Option<Duration>,
+    pub best_race: Option<Duration>,
+    /// Time since the start of the lap at each checkpoint of the best lap
+    pub best_splits: Vec<Duration>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct Save {
+    version: u32,
+    #[serde(deserialize_with = "deserialize_records")]
+    records: Vec<Record>,
+}
+
+impl SaveFormat for Save {
+    const VERSION: u32 = 1;
+}
+
+/// Reads the records one by one, so an invalid record is skipped instead of
+/// losing the whole leaderboard
+fn deserialize_records<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Record>, D::Error> {
+    let records: Vec<ron::Value> = serde::Deserialize::deserialize(deserializer)?;
+
+    Ok(records
+        .into_iter()
+        .filter_map(|record| {
+            record
+                .into_rust()
+                .map_err(|err| tracing::warn!("Skipping an invalid leaderboard record: {err}"))
+                .ok()
+        })
+        .collect())
+}
+
+/// Best lap and race times of the player, per track and kart, kept between
+/// sessions
+#[derive(Debug, Resource, Default)]
+pub struct Leaderboard {
+    records: Vec<Record>,
+}
+
+impl Leaderboard {
+    /// Reads the saved leaderboard, an empty one if there is none or it can't
+    /// be read
+    pub fn load() -> Self {
+        let save: Option<Save> = save::load(SAVE_NAME);
+
+        Self {
+            records: save.map_or(Vec::new(), |save| save.records),
+        }
+    }
+
+    pub fn save(&self) {
+        save::store(
+            SAVE_NAME,
+            &Save {
+                version: Save::VERSION,
+                records: self.records.clone(),
+            },
+        );
+    }
+
+    /// Record of the kart on the track, the personal best the race is timed
+    /// against
+    pub fn record(&self, track: &TrackId, kart: &KartId) -> Option<&Record> {
+        self.records
+            .iter()
+            .find(|record| record.track == *track && record.kart == *kart)
+    }
+
+    /// Records of the track, fastest race first
+    pub fn track_records(&self, track: &TrackId) -> Vec<&Record> {
+        let mut records: Vec<&Record> = self
+            .records
+            .iter()
+            .filter(|record| record.track == *track)
+            .collect();
+
+        // Records without a race time come last
+        records.sort_by_key(|record| (record.best_race.is_none(), record.best_race));
+        records
+    }
+
+    /// Keeps the times that beat the record of the kart on the track, returns
+    /// whether any did
+    ///
+    /// `lap_splits` are the splits of the `lap`, kept with it
+    pub fn submit(
+        &mut self,
+        track: &TrackId,
+        kart: &KartId,
+        lap: Option<Duration>,
+        lap_splits: &[Duration],
+        race: Option<Duration>,
+    ) -> bool {
+        let index = self
+            .records
+            .iter()
+            .position(|record| record.track == *track && record.kart == *kart)
+            .unwrap_or_else(|| {
+                self.records.push(Record {
+                    track: track.clone(),
+                    kart: kart.clone(),
+                    best_lap: None,
+                    best_race: None,
+                    best_splits: Vec::new(),
+                });
+                self.records.len() - 1
+            });
+        let record = &mut self.records[index];
+
+        let is_best = |best: Option<Duration>, time: Option<Duration>| match (best, time) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(best), Some(time)) => time < best,
+        };
+
+        let is_best_lap = is_best(record.best_lap, lap);
+        if is_best_lap {
+            record.best_lap = lap;
+            record.best_splits = lap_splits.to_vec();
+        }
+        let is_best_race = is_best(record.best_race, race);
+        if is_best_race {
+            record.best_race = race;
+        }
+
+        is_best_lap || is_best_race
+    }
+}
+
+/// Root of the leaderboard's UI
+#[derive(Debug, Component)]
+pub(super) struct LeaderboardScreen;
+
+/// Submits the player's times once they finished a time trial
+pub(super) fn record_player_times(
+    track: Res<Track>,
+    mut leaderboard: ResMut<Leaderboard>,
+    timings: Res<RaceTimings>,
+    players: Query<(Entity, &KartId), With<Player>>,
+) {
+    for (kart, kart_id) in players.iter() {
+        let Some(timing) = timings.get(kart) else {
+            continue;
+        };
+
+        if leaderboard.submit(
+            &track.id,
+            kart_id,
+            timing.best_lap,
+            &timing.best_splits,
+            timing.total,
+        ) {
+            tracing::info!("New record on {} with {}", track.name, kart_id.0);
+            leaderboard.save();
+        }
+    }
+}
+
+pub(super) fn show_leaderboard(
+    mut commands: Commands,
+    track: Res<Track>,
+    leaderboard: Res<Leaderboard>,
+    kart_registry: Res<KartRegistry>,
+) {
+    let format_time = |time: Option<Duration>| time.map_or("-".to_string(), format_race_time);
+
+    let records = leaderboard.track_records(&track.id);
+    let lines: Vec<String> = records
+        .iter()
+        .map(|record| {
+            let name = kart_registry
+                .get(&record.kart)
+                .map_or(record.kart.0.as_str(), |entry| entry.name.as_str());
+
+            format!(
+                "{name} - race {} - lap {}",
+                format_time(record.best_race),
+                format_time(record.best_lap)
+            )
+        })
+        .collect();
+
+    commands
+        .spawn((screen_node(), LeaderboardScreen))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("{} records", track.name),
+                text_style(60f32),
+            ));
+            if lines.is_empty() {
+                parent.spawn(TextBundle::from_section(
+                    "No records yet",
+                    text_style(30f32),
+                ));
+            }
+            for line in lines {
+                parent.spawn(TextBundle::from_section(line, text_style(30f32)));
+            }
+            parent.spawn(TextBundle::from_section(
+                "Press jump to race again",
+                text_style(20f32),
+            ));
+        });
+}
+
+pub(super) fn hide_leaderboard(
+    mut commands: Commands,
+    query: Query<Entity, With<LeaderboardScreen>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_records_are_skipped() {
+        let save: Save = ron::from_str(
+            r#"(
+                version: 1,
+                records: [
+                    (track: "map01", kart: "sedan", best_lap: Some((secs: 30, nanos: 0)), best_race: None, best_splits: []),
+                    (track: "map01", kart: 12, best_lap: None, best_race: None, best_splits: []),
+                    (track: "map01", kart: "police", best_lap: None, best_race: Some("fast"), best_splits: []),
+                    (track: "map01", kart: "muscle", best_lap: None, best_race: None),
+                    (track: "map02", kart: "police", best_lap: None, best_race: Some((secs: 95, nanos: 500)), best_splits: []),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        let karts: Vec<&str> = save
+            .records
+            .iter()
+            .map(|record| record.kart.0.as_str())
+            .collect();
+        assert_eq!(karts, ["sedan", "police"]);
+        assert_eq!(save.records[0].best_lap, Some(Duration::from_secs(30)));
+        assert_eq!(save.records[1].best_race, Some(Duration::new(95, 500)));
+    }
+
+    #[test]
+    fn saved_records_are_read_back() {
+        let mut leaderboard = Leaderboard::default();
+        let (track, kart) = (TrackId("map01".to_string()), KartId("sedan".to_string()));
+        let splits = [Duration::from_secs(10), Duration::from_secs(20)];
+        leaderboard.submit(&track, &kart, Some(Duration::from_secs(30)), &splits, None);
+
+        let content = ron::to_string(&Save {
+            version: Save::VERSION,
+            records: leaderboard.records.clone(),
+        })
+        .unwrap();
+        let save: Save = ron::from_str(&content).unwrap();
+
+        assert_eq!(save.records.len(), 1);
+        assert_eq!(save.records[0].track, track);
+        assert_eq!(save.records[0].best_lap, Some(Duration::from_secs(30)));
+        assert_eq!(save.records[0].best_splits, splits);
+    }
+
+    #[test]
+    fn only_better_times_are_kept() {
+        let mut leaderboard = Leaderboard::default();
+        let (track, kart) = (TrackId("map01".to_string()), KartId("sedan".to_string()));
+        let seconds = |seconds| Some(Duration::from_secs(seconds));
+
+        let splits = |seconds| [Duration::from_secs(seconds)];
+
+        assert!(leaderboard.submit(&track, &kart, seconds(30), &splits(10), seconds(100)));
+        assert!(!leaderboard.submit(&track, &kart, seconds(31), &splits(9), seconds(100)));
+        assert!(leaderboard.submit(&track, &kart, seconds(32), &splits(8), seconds(99)));
+
+        let records = leaderboard.track_records(&track);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].best_lap, seconds(30));
+        assert_eq!(records[0].best_race, seconds(99));
+        // The splits are those of the best lap
+        assert_eq!(records[0].best_splits, splits(10));
+    }
+}
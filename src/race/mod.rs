@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::PhysicsSet;
+
+use crate::{assets::AssetLoadingState, scene::SceneReadySet};
+
+pub mod checkpoint;
+pub mod ghost;
+pub mod leaderboard;
+pub mod ranking;
+pub mod respawn;
+pub mod state;
+pub mod timing;
+pub mod wrong_way;
+
+use state::RaceState;
+
+/// Formats a race time as `minutes:seconds.milliseconds`
+pub fn format_race_time(time: Duration) -> String {
+    let milliseconds = time.as_millis();
+
+    format!(
+        "{}:{:02}.{:03}",
+        milliseconds / 60_000,
+        milliseconds / 1000 % 60,
+        milliseconds % 1000
+    )
+}
+
+/// Kind of race, see [`RaceMode::from_args`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub enum RaceMode {
+    /// The player alone against the clock, and the ghost of their best race
+    TimeTrial,
+    /// The player against other karts
+    Race,
+}
+
+impl RaceMode {
+    /// A [`RaceMode::Race`] with the `--race` command line argument, a
+    /// [`RaceMode::TimeTrial`] otherwise
+    pub fn from_args() -> Self {
+        if std::env::args().any(|arg| arg == "--race") {
+            Self::Race
+        } else {
+            Self::TimeTrial
+        }
+    }
+}
+
+pub struct RacePlugin;
+
+impl Plugin for RacePlugin {
+    fn build(&self, app: &mut App) {
+        // Needed for the `ComponentsFromGltfPlugin`
+        app.register_type::<checkpoint::Checkpoint>();
+        app.register_type::<checkpoint::FinishLine>();
+        app.register_type::<respawn::KillVolume>();
+
+        app.add_event::<checkpoint::CheckpointPassed>();
+        app.add_event::<checkpoint::LapStarted>();
+        app.add_event::<checkpoint::LapCompleted>();
+        app.add_event::<checkpoint::RaceFinished>();
+        app.add_event::<wrong_way::WrongWayChanged>();
+
+        app.add_state::<RaceState>();
+        app.init_resource::<checkpoint::TrackCheckpoints>();
+        app.init_resource::<state::RaceResults>();
+        app.init_resource::<timing::RaceClock>();
+        app.init_resource::<timing::RaceTimings>();
+        app.init_resource::<ranking::RaceRanking>();
+        app.insert_resource(leaderboard::Leaderboard::load());
+        app.init_resource::<ghost::BestGhost>();
+        app.init_resource::<ghost::GhostRecording>();
+
+        app.add_systems(
+            Update,
+            (
+                checkpoint::init_checkpoints,
+                checkpoint::collect_checkpoints.in_set(SceneReadySet),
+                respawn::init_kill_volumes,
+                checkpoint::log_race_events,
+            )
+                .run_if(in_state(AssetLoadingState::Done)),
+        );
+
+        app.add_systems(
+            FixedUpdate,
+            (
+                timing::tick_race_clock,
+                // Samples are the karts' transforms once the step moved them
+                (
+                    ghost::play_ghost,
+                    ghost::record_ghost.run_if(
+                        in_state(RaceState::Racing).and_then(resource_equals(RaceMode::TimeTrial)),
+                    ),
+                )
+                    .after(PhysicsSet::Writeback),
+            )
+                .run_if(in_state(RaceState::Racing).or_else(in_state(RaceState::Finished))),
+        );
+        // Checkpoints are crossed on the physics steps, and timed with the race
+        // clock of the same step
+        app.add_systems(
+            FixedUpdate,
+            (
+                checkpoint::update_lap_progress,
+                timing::update_timing,
+                state::record_finishers,
+            )
+                .chain()
+                .after(PhysicsSet::Writeback)
+                .after(timing::tick_race_clock)
+                .run_if(in_state(RaceState::Racing).or_else(in_state(RaceState::Finished))),
+        );
+
+        app.add_systems(OnEnter(AssetLoadingState::Done), ghost::load_best_ghost);
+        app.add_systems(
+            OnEnter(RaceState::Countdown),
+            (
+                state::start_countdown,
+                ghost::spawn_ghost,
+                ranking::show_position.run_if(resource_equals(RaceMode::Race)),
+                wrong_way::show_wrong_way,
+            ),
+        );
+        app.add_systems(
+            OnEnter(RaceState::Racing),
+            (state::start_race, timing::start_timing),
+        );
+        app.add_systems(
+            OnEnter(RaceState::Finished),
+            (
+                state::start_finished,
+                // Only time trials are records, opponents get in the way
+                (leaderboard::record_player_times, ghost::save_ghost)
+                    .run_if(resource_equals(RaceMode::TimeTrial)),
+            ),
+        );
+        app.add_systems(
+            OnEnter(RaceState::Results),
+            (
+                state::show_results,
+                ranking::hide_position,
+                wrong_way::hide_wrong_way,
+            ),
+        );
+        app.add_systems(OnExit(RaceState::Results), state::hide_results);
+        app.add_systems(
+            OnEnter(RaceState::Leaderboard),
+            leaderboard::show_leaderboard,
+        );
+        app.add_systems(
+            OnExit(RaceState::Leaderboard),
+            leaderboard::hide_leaderboard,
+        );
+
+        app.add_systems(
+            Update,
+            (
+                state::place_karts_on_grid.run_if(in_state(RaceState::Grid)),
+                state::update_countdown.run_if(in_state(RaceState::Countdown)),
+                state::hide_countdown.run_if(in_state(RaceState::Racing)),
+                (ranking::update_ranking, ranking::update_position)
+                    .chain()
+                    .run_if(
+                        in_state(RaceState::Countdown)
+                            .or_else(in_state(RaceState::Racing))
+                            .or_else(in_state(RaceState::Finished)),
+                    ),
+                (
+                    wrong_way::detect_wrong_way,
+                    wrong_way::update_wrong_way_warning,
+                )
+                    .chain()
+                    .run_if(in_state(RaceState::Racing).or_else(in_state(RaceState::Finished))),
+                state::update_finished.run_if(in_state(RaceState::Finished)),
+                state::continue_on_jump
+                    .run_if(in_state(RaceState::Results).or_else(in_state(RaceState::Leaderboard))),
+                ghost::make_ghost_translucent.in_set(SceneReadySet),
+            )
+                .run_if(in_state(AssetLoadingState::Done)),
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+
+use super::{
+    checkpoint::{FinishLine, LapProgress, TrackCheckpoints},
+    state::{text_style, RaceResults},
+};
+use crate::{kart::Player, track::racing_line::RacingLine};
+
+/// Place of the kart in the race
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct RacePosition {
+    /// From 1 for the leader to the number of karts
+    pub rank: usize,
+    /// Distance in meters covered since the start of the first lap, along the
+    /// track's [`RacingLine`], negative while on the grid
+    pub distance: f32,
+}
+
+/// Karts of the race from the leader to the last one, see [`RacePosition`]
+#[derive(Debug, Resource, Default)]
+pub struct RaceRanking {
+    pub karts: Vec<Entity>,
+}
+
+/// Root of the position's UI, shown while racing
+#[derive(Debug, Component)]
+pub(super) struct PositionHud;
+
+#[derive(Debug, Component)]
+pub(super) struct PositionText;
+
+/// `rank` as an English ordinal, e.g. "3rd"
+fn ordinal(rank: usize) -> String {
+    let suffix = match (rank % 10, rank % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+
+    format!("{rank}{suffix}")
+}
+
+/// Distance of the kart along the [`RacingLine`] from the finish line, in the
+/// current lap
+///
+/// The kart's progress through the checkpoints tells on which side of the
+/// finish line it is, while its chassis is still in the line's volume
+fn lap_distance(
+    racing_line: &RacingLine,
+    finish_distance: f32,
+    position: Vec3,
+    progress: &LapProgress,
+    checkpoint_count: u32,
+) -> f32 {
+    if !racing_line.is_valid() {
+        return 0f32;
+    }
+
+    let length = racing_line.length();
+    let distance = (racing_line.closest(position).distance - finish_distance).rem_euclid(length);
+
+    if progress.next_checkpoint == 0 && distance > length / 2f32 {
+        // Before the finish line, e.g. on the grid
+        distance - length
+    } else if checkpoint_count > 0
+        && progress.next_checkpoint == checkpoint_count
+        && distance < length / 2f32
+    {
+        // Past the finish line, before the lap is counted
+        distance + length
+    } else {
+        distance
+    }
+}
+
+/// Ranks the karts, the finishers in finishing order then the others by lap,
+/// checkpoint and distance along the [`RacingLine`]
+pub(super) fn update_ranking(
+    racing_line: Res<RacingLine>,
+    results: Res<RaceResults>,
+    mut ranking: ResMut<RaceRanking>,
+    track_checkpoints: Res<TrackCheckpoints>,
+    finish_lines: Query<&GlobalTransform, With<FinishLine>>,
+    mut karts: Query<(Entity, &Transform, &LapProgress, &mut RacePosition)>,
+) {
+    let checkpoint_count = track_checkpoints.count().unwrap_or(0);
+    let finish_distance = match finish_lines.iter().next() {
+        Some(transform) if racing_line.is_valid() => {
+            racing_line.closest(transform.translation()).distance
+        }
+        _ => 0f32,
+    };
+
+    let mut racing: Vec<(Entity, u32, u32, f32)> = Vec::new();
+    for (entity, transform, progress, mut position) in karts.iter_mut() {
+        let distance = lap_distance(
+            &racing_line,
+            finish_distance,
+            transform.translation,
+            progress,
+            checkpoint_count,
+        );
+        position.distance = progress.lap.saturating_sub(1) as f32 * racing_line.length() + distance;
+
+        if !progress.finished {
+            racing.push((
+                entity,
+                progress.lap,
+                progress.next_checkpoint,
+                position.distance,
+            ));
+        }
+    }
+    racing.sort_by(|a, b| {
+        (b.1, b.2)
+            .cmp(&(a.1, a.2))
+            .then_with(|| b.3.total_cmp(&a.3))
+    });
+
+    ranking.karts = results
+        .finishers
+        .iter()
+        .map(|finisher| finisher.kart)
+        .filter(|kart| karts.contains(*kart))
+        .chain(racing.into_iter().map(|(entity, ..)| entity))
+        .collect();
+
+    for (index, kart) in ranking.karts.iter().enumerate() {
+        if let Ok((_, _, _, mut position)) = karts.get_mut(*kart) {
+            position.rank = index + 1;
+        }
+    }
+}
+
+pub(super) fn show_position(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(20f32),
+                    right: Val::Px(20f32),
+                    ..default()
+                },
+                ..default()
+            },
+            PositionHud,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", text_style(40f32)),
+                PositionText,
+            ));
+        });
+}
+
+/// Shows the player's position as e.g. "3rd / 8"
+pub(super) fn update_position(
+    ranking: Res<RaceRanking>,
+    players: Query<&RacePosition, With<Player>>,
+    mut query: Query<&mut Text, With<PositionText>>,
+) {
+    let Ok(position) = players.get_single() else {
+        return;
+    };
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("{} / {}", ordinal(position.rank), ranking.karts.len());
+    }
+}
+
+pub(super) fn hide_position(mut commands: Commands, query: Query<Entity, With<PositionHud>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordinals_follow_english() {
+        let ordinals: Vec<String> = [1, 2, 3, 4, 10, 11, 12, 13, 21, 22, 23, 101, 111, 112, 113]
+            .into_iter()
+            .map(ordinal)
+            .collect();
+
+        assert_eq!(
+            ordinals,
+            [
+                "1st", "2nd", "3rd", "4th", "10th", "11th", "12th", "13th", "21st", "22nd", "23rd",
+                "101st", "111th", "112th", "113th"
+            ]
+        );
+    }
+
+    fn progress(lap: u32, next_checkpoint: u32) -> LapProgress {
+        LapProgress {
+            lap,
+            next_checkpoint,
+            finished: false,
+        }
+    }
+
+    #[test]
+    fn lap_distance_is_measured_from_the_finish_line() {
+        let line = RacingLine::from_waypoints(&[
+            (Vec3::new(0f32, 0f32, 0f32), 5f32),
+            (Vec3::new(40f32, 0f32, 0f32), 5f32),
+            (Vec3::new(40f32, 0f32, 40f32), 5f32),
+            (Vec3::new(0f32, 0f32, 40f32), 5f32),
+        ]);
+        let length = line.length();
+        let finish_distance = 10f32;
+        let distance = |distance: f32, progress: LapProgress| {
+            let position = line.sample(finish_distance + distance).point + Vec3::Y;
+            lap_distance(&line, finish_distance, position, &progress, 2)
+        };
+        let assert_near = |distance: f32, expected: f32| {
+            assert!(
+                (distance - expected).abs() < 0.1,
+                "{distance} != {expected}"
+            );
+        };
+
+        // On the grid, behind the finish line
+        assert_near(distance(-5f32, progress(0, 0)), -5f32);
+        // Started the lap
+        assert_near(distance(5f32, progress(1, 0)), 5f32);
+        // Late in the lap, past the checkpoints
+        assert_near(distance(length - 5f32, progress(1, 2)), length - 5f32);
+        // Late in the lap, before the first checkpoint after going back
+        assert_near(distance(length - 5f32, progress(1, 1)), length - 5f32);
+        // Across the finish line, before the lap is counted
+        assert_near(distance(3f32, progress(1, 2)), length + 3f32);
+    }
+
+    #[test]
+    fn lap_distance_needs_a_racing_line() {
+        let distance = lap_distance(&RacingLine::default(), 0f32, Vec3::ONE, &progress(1, 0), 2);
+        assert_eq!(distance, 0f32);
+    }
+}
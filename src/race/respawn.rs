@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    checkpoint::{Checkpoint, FinishLine, LapProgress},
+    state::GridSlot,
+};
+use crate::{
+    kart::{
+        drift::{Boost, Drift},
+        Chassis, KartInput, Speed,
+    },
+    track::{registry::Track, spawn::SpawnPoints},
+};
+
+/// Seconds the kart is held still after a respawn, the penalty for leaving
+/// the track
+const RESPAWN_SECONDS: f32 = 1.5f32;
+/// Height above the checkpoint's center where the kart respawns
+const RESPAWN_HEIGHT: f32 = 0.5f32;
+
+/// A volume sending the karts that enter it back on the track, e.g. under a
+/// bridge or in the water
+///
+/// Set from the node's custom properties, e.g. `KillVolume: ""`. The volume
+/// is the node's cube from -1 to 1 scaled by the node, like a [`Checkpoint`]
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct KillVolume;
+
+/// A kart held still where it respawned, until the timer ends
+#[derive(Debug, Component)]
+pub struct Respawning {
+    transform: Transform,
+    timer: Timer,
+}
+
+/// Turns the [`KillVolume`] nodes of the track into hidden sensors
+pub fn init_kill_volumes(mut commands: Commands, volumes: Query<Entity, Added<KillVolume>>) {
+    for entity in volumes.iter() {
+        commands.entity(entity).insert((
+            Collider::cuboid(1f32, 1f32, 1f32),
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            Visibility::Hidden,
+        ));
+    }
+}
+
+/// Transform without the scale of the volume it comes from
+fn unscaled(transform: &GlobalTransform) -> Transform {
+    let (_, rotation, translation) = transform.to_scale_rotation_translation();
+    Transform::from_translation(translation + Vec3::Y * RESPAWN_HEIGHT).with_rotation(rotation)
+}
+
+/// Where the kart respawns: on the last checkpoint it passed, facing the way
+/// through it, or on the finish line or its grid slot before the first
+/// checkpoint
+fn respawn_transform(
+    progress: &LapProgress,
+    slot: &GridSlot,
+    track: &Track,
+    spawn_points: &SpawnPoints,
+    checkpoints: &Query<(&Checkpoint, &GlobalTransform)>,
+    finish_lines: &Query<&GlobalTransform, With<FinishLine>>,
+) -> Transform {
+    let last_checkpoint = progress.next_checkpoint.checked_sub(1).and_then(|index| {
+        checkpoints
+            .iter()
+            .find(|(checkpoint, _)| checkpoint.index == index)
+    });
+    if let Some((_, transform)) = last_checkpoint {
+        return unscaled(transform);
+    }
+
+    match finish_lines.iter().next() {
+        Some(transform) if progress.lap > 0 => unscaled(transform),
+        _ => spawn_points.slot(slot.0, &track.spawn_grid),
+    }
+}
+
+/// Respawns the karts that fell under the track, went in a [`KillVolume`] or
+/// asked to with [`KartInput::respawn`]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn detect_out_of_bounds(
+    mut commands: Commands,
+    track: Res<Track>,
+    spawn_points: Res<SpawnPoints>,
+    mut collision_events: EventReader<CollisionEvent>,
+    kill_volumes: Query<(), With<KillVolume>>,
+    chassis: Query<&Parent, With<Chassis>>,
+    checkpoints: Query<(&Checkpoint, &GlobalTransform)>,
+    finish_lines: Query<&GlobalTransform, With<FinishLine>>,
+    karts: Query<(Entity, &Transform, &KartInput, &LapProgress, &GridSlot), Without<Respawning>>,
+) {
+    // Karts are killed by their chassis only, like they pass checkpoints
+    let mut killed: Vec<Entity> = collision_events
+        .iter()
+        .filter_map(|collision_event| match *collision_event {
+            CollisionEvent::Started(first, second, _) => Some((first, second)),
+            CollisionEvent::Stopped(..) => None,
+        })
+        .filter_map(|(first, second)| {
+            let collider = if kill_volumes.contains(first) {
+                second
+            } else if kill_volumes.contains(second) {
+                first
+            } else {
+                return None;
+            };
+            chassis.get(collider).ok().map(|parent| parent.get())
+        })
+        .collect();
+
+    for (kart, transform, input, _, _) in karts.iter() {
+        if transform.translation.y < track.kill_y || input.respawn {
+            killed.push(kart);
+        }
+    }
+
+    killed.sort();
+    killed.dedup();
+    for kart in killed {
+        let Ok((_, _, _, progress, slot)) = karts.get(kart) else {
+            continue;
+        };
+
+        tracing::info!("Respawning kart {kart:?}");
+        commands
+            .entity(kart)
+            .remove::<(Drift, Boost)>()
+            .insert(Respawning {
+                transform: respawn_transform(
+                    progress,
+                    slot,
+                    &track,
+                    &spawn_points,
+                    &checkpoints,
+                    &finish_lines,
+                ),
+                timer: Timer::from_seconds(RESPAWN_SECONDS, TimerMode::Once),
+            });
+    }
+}
+
+/// Holds the respawning karts still on their respawn point, with no input,
+/// until the penalty is over
+pub fn update_respawn(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    mut karts: Query<(
+        Entity,
+        &mut Respawning,
+        &mut Transform,
+        &mut Velocity,
+        &mut Speed,
+        &mut KartInput,
+    )>,
+) {
+    for (entity, mut respawning, mut transform, mut velocity, mut speed, mut input) in
+        karts.iter_mut()
+    {
+        *transform = respawning.transform;
+        *velocity = Velocity::zero();
+        *speed = Speed::default();
+        *input = KartInput::default();
+
+        if respawning.timer.tick(fixed_time.period).just_finished() {
+            commands.entity(entity).remove::<Respawning>();
+        }
+    }
+}
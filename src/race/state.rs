@@ -0,0 +1,351 @@
+use std::time::Duration;
+
+use bevy::{ecs::query::Has, prelude::*};
+use bevy_rapier3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use super::{
+    checkpoint::{LapProgress, RaceFinished},
+    format_race_time,
+    ranking::{RacePosition, RaceRanking},
+    respawn::Respawning,
+    timing::{RaceClock, RaceTimings},
+    wrong_way::WrongWay,
+};
+use crate::{
+    input::Action,
+    kart::{
+        drift::{Boost, Drift},
+        registry::{KartId, KartRegistry},
+        Player, Speed,
+    },
+    track::{registry::Track, spawn::SpawnPoints},
+};
+
+/// Seconds counted down before the race starts
+const COUNTDOWN_SECONDS: f32 = 3f32;
+/// Seconds "GO!" stays on screen once the race started
+const GO_SECONDS: f32 = 1f32;
+/// Seconds between the player crossing the finish line and the results
+const FINISHED_SECONDS: f32 = 3f32;
+
+/// Phase of the race, once the assets are loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+pub enum RaceState {
+    /// The karts are put back on the starting grid
+    #[default]
+    Grid,
+    /// The karts wait on the grid for the countdown to end
+    Countdown,
+    Racing,
+    /// The player finished, the race goes on for a few seconds
+    Finished,
+    /// Finishing order and times
+    Results,
+    /// Records of the track, until the player restarts the race
+    Leaderboard,
+}
+
+/// Slot of the kart on the track's [`crate::track::SpawnGrid`]
+#[derive(Debug, Component, Clone, Copy)]
+pub struct GridSlot(pub usize);
+
+/// A kart that completed the race
+#[derive(Debug)]
+pub struct Finisher {
+    pub kart: Entity,
+    /// Time of the whole race, see [`RaceClock`]
+    pub time: Duration,
+}
+
+/// Karts that completed the race, in finishing order
+#[derive(Debug, Resource, Default)]
+pub struct RaceResults {
+    pub finishers: Vec<Finisher>,
+}
+
+/// Timer of the phases that end on their own
+#[derive(Debug, Resource)]
+pub(super) struct PhaseTimer(Timer);
+
+impl PhaseTimer {
+    fn from_seconds(seconds: f32) -> Self {
+        Self(Timer::from_seconds(seconds, TimerMode::Once))
+    }
+}
+
+/// Root of the countdown's UI, stays until "GO!" is hidden
+#[derive(Debug, Component)]
+pub(super) struct CountdownScreen;
+
+#[derive(Debug, Component)]
+pub(super) struct CountdownText;
+
+/// Root of the results' UI
+#[derive(Debug, Component)]
+pub(super) struct ResultsScreen;
+
+/// Full screen node centering its children
+pub(super) fn screen_node() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100f32),
+            height: Val::Percent(100f32),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+pub(super) fn text_style(font_size: f32) -> TextStyle {
+    TextStyle {
+        font_size,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+/// Puts every kart back on its [`GridSlot`], stopped and with no progress
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(super) fn place_karts_on_grid(
+    mut commands: Commands,
+    track: Res<Track>,
+    spawn_points: Res<SpawnPoints>,
+    mut results: ResMut<RaceResults>,
+    mut clock: ResMut<RaceClock>,
+    mut timings: ResMut<RaceTimings>,
+    mut next_state: ResMut<NextState<RaceState>>,
+    mut karts: Query<(
+        Entity,
+        &GridSlot,
+        &mut Transform,
+        &mut Velocity,
+        &mut Speed,
+        &mut LapProgress,
+        &mut WrongWay,
+    )>,
+) {
+    // The karts are spawned with the track, the spawn points are known once
+    // its scene is spawned
+    if karts.is_empty() || !spawn_points.is_ready() {
+        return;
+    }
+
+    for (entity, slot, mut transform, mut velocity, mut speed, mut progress, mut wrong_way) in
+        karts.iter_mut()
+    {
+        *transform = spawn_points.slot(slot.0, &track.spawn_grid);
+        *velocity = Velocity::zero();
+        *speed = Speed::default();
+        *progress = LapProgress::default();
+        *wrong_way = WrongWay::default();
+        commands
+            .entity(entity)
+            .remove::<(Drift, Boost, Respawning)>();
+    }
+
+    *results = RaceResults::default();
+    *clock = RaceClock::default();
+    *timings = RaceTimings::default();
+    next_state.set(RaceState::Countdown);
+}
+
+pub(super) fn start_countdown(mut commands: Commands) {
+    commands.insert_resource(PhaseTimer::from_seconds(COUNTDOWN_SECONDS));
+
+    commands
+        .spawn((screen_node(), CountdownScreen))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", text_style(120f32)),
+                CountdownText,
+            ));
+        });
+}
+
+pub(super) fn update_countdown(
+    time: Res<Time>,
+    mut timer: ResMut<PhaseTimer>,
+    mut next_state: ResMut<NextState<RaceState>>,
+    mut query: Query<&mut Text, With<CountdownText>>,
+) {
+    timer.0.tick(time.delta());
+
+    let remaining = timer.0.remaining_secs().ceil();
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("{remaining}");
+    }
+
+    if timer.0.finished() {
+        next_state.set(RaceState::Racing);
+    }
+}
+
+pub(super) fn start_race(mut commands: Commands, mut query: Query<&mut Text, With<CountdownText>>) {
+    commands.insert_resource(PhaseTimer::from_seconds(GO_SECONDS));
+    for mut text in query.iter_mut() {
+        text.sections[0].value = "GO!".to_string();
+    }
+}
+
+pub(super) fn hide_countdown(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut timer: ResMut<PhaseTimer>,
+    query: Query<Entity, With<CountdownScreen>>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Adds the karts finishing the race to the [`RaceResults`], the race is
+/// [`RaceState::Finished`] once the player finished
+///
+/// Karts finishing on the same step are ordered by their [`RacePosition`]
+/// before the line
+pub(super) fn record_finishers(
+    clock: Res<RaceClock>,
+    mut race_finished: EventReader<RaceFinished>,
+    mut results: ResMut<RaceResults>,
+    mut next_state: ResMut<NextState<RaceState>>,
+    players: Query<(), With<Player>>,
+    positions: Query<&RacePosition>,
+) {
+    let mut finishers: Vec<Entity> = race_finished.iter().map(|event| event.kart).collect();
+    finishers.sort_by_key(|kart| {
+        positions
+            .get(*kart)
+            .map_or(usize::MAX, |position| position.rank)
+    });
+
+    for kart in finishers {
+        results.finishers.push(Finisher {
+            kart,
+            time: clock.elapsed,
+        });
+
+        if players.contains(kart) {
+            next_state.set(RaceState::Finished);
+        }
+    }
+}
+
+pub(super) fn start_finished(mut commands: Commands, query: Query<Entity, With<CountdownScreen>>) {
+    commands.insert_resource(PhaseTimer::from_seconds(FINISHED_SECONDS));
+
+    // In case the race was finished before "GO!" was hidden
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub(super) fn update_finished(
+    time: Res<Time>,
+    mut timer: ResMut<PhaseTimer>,
+    mut next_state: ResMut<NextState<RaceState>>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        next_state.set(RaceState::Results);
+    }
+}
+
+/// Lists the finishers in order with their time, then the karts that didn't
+/// finish in their race order
+pub(super) fn show_results(
+    mut commands: Commands,
+    results: Res<RaceResults>,
+    ranking: Res<RaceRanking>,
+    kart_registry: Res<KartRegistry>,
+    karts: Query<(Entity, &KartId, Has<Player>), With<LapProgress>>,
+    timings: Res<RaceTimings>,
+) {
+    let kart_name = |kart: Entity| {
+        let Ok((_, kart_id, is_player)) = karts.get(kart) else {
+            return "Unknown kart".to_string();
+        };
+
+        let name = kart_registry
+            .get(kart_id)
+            .map_or(kart_id.0.as_str(), |entry| entry.name.as_str());
+        if is_player {
+            format!("{name} (you)")
+        } else {
+            name.to_string()
+        }
+    };
+
+    let mut lines: Vec<String> = results
+        .finishers
+        .iter()
+        .enumerate()
+        .map(|(index, finisher)| {
+            let best_lap = timings
+                .get(finisher.kart)
+                .and_then(|timing| timing.best_lap)
+                .map_or(String::new(), |best_lap| {
+                    format!(" (best lap {})", format_race_time(best_lap))
+                });
+
+            format!(
+                "{}. {} - {}{best_lap}",
+                index + 1,
+                kart_name(finisher.kart),
+                format_race_time(finisher.time)
+            )
+        })
+        .collect();
+
+    let has_finished = |kart: Entity| {
+        results
+            .finishers
+            .iter()
+            .any(|finisher| finisher.kart == kart)
+    };
+    for kart in ranking.karts.iter().filter(|kart| !has_finished(**kart)) {
+        lines.push(format!("-. {} - DNF", kart_name(*kart)));
+    }
+
+    commands
+        .spawn((screen_node(), ResultsScreen))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Results", text_style(60f32)));
+            for line in lines {
+                parent.spawn(TextBundle::from_section(line, text_style(30f32)));
+            }
+            parent.spawn(TextBundle::from_section(
+                "Press jump to continue",
+                text_style(20f32),
+            ));
+        });
+}
+
+/// Goes from the results to the leaderboard, then restarts the race
+pub(super) fn continue_on_jump(
+    action_state: Res<ActionState<Action>>,
+    race_state: Res<State<RaceState>>,
+    mut next_state: ResMut<NextState<RaceState>>,
+) {
+    if !action_state.just_pressed(Action::Jump) {
+        return;
+    }
+
+    match race_state.get() {
+        RaceState::Results => next_state.set(RaceState::Leaderboard),
+        RaceState::Leaderboard => next_state.set(RaceState::Grid),
+        _ => {}
+    }
+}
+
+pub(super) fn hide_results(mut commands: Commands, query: Query<Entity, With<ResultsScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    checkpoint::{CheckpointPassed, LapCompleted, LapProgress, LapStarted, RaceFinished},
+    format_race_time,
+    leaderboard::Leaderboard,
+};
+use crate::{
+    kart::{registry::KartId, Player},
+    track::registry::Track,
+};
+
+/// Time since the start of the race, advanced on the [`FixedUpdate`] steps like
+/// the physics, see [`crate::physics::FixedStepPhysicsPlugin`], so the same race
+/// always gives the same times
+#[derive(Debug, Resource, Default)]
+pub struct RaceClock {
+    pub elapsed: Duration,
+}
+
+pub fn tick_race_clock(fixed_time: Res<FixedTime>, mut clock: ResMut<RaceClock>) {
+    clock.elapsed += fixed_time.period;
+}
+
+/// Lap times and splits of a kart, in [`RaceClock`] time
+#[derive(Debug, Default)]
+pub struct KartTiming {
+    /// When each lap started, from lap 1
+    lap_starts: Vec<Duration>,
+    /// Times of the completed laps, from lap 1
+    pub laps: Vec<Duration>,
+    pub best_lap: Option<Duration>,
+    /// Time since the start of the lap at each checkpoint passed this lap
+    pub splits: Vec<Duration>,
+    /// The `splits` of the best lap
+    pub best_splits: Vec<Duration>,
+    /// Seconds gained (negative) or lost (positive) against the personal best
+    /// of the player's kart on the track, see [`Leaderboard::record`], at the
+    /// last checkpoint or finish line crossed
+    pub delta: Option<f32>,
+    /// Time of the whole race, once finished
+    pub total: Option<Duration>,
+}
+
+impl KartTiming {
+    /// Time since the start of the current lap
+    pub fn current_lap(&self, clock: &RaceClock) -> Option<Duration> {
+        let lap_start = self.lap_starts.last()?;
+        Some(clock.elapsed.saturating_sub(*lap_start))
+    }
+}
+
+/// Timings of the karts in the race, reset with it
+#[derive(Debug, Resource, Default)]
+pub struct RaceTimings {
+    karts: HashMap<Entity, KartTiming>,
+}
+
+impl RaceTimings {
+    /// Timing of a kart, `None` until the race starts
+    pub fn get(&self, kart: Entity) -> Option<&KartTiming> {
+        self.karts.get(&kart)
+    }
+}
+
+/// Starts the first lap of every kart with the race, the time they take to
+/// reach the finish line from the grid is part of it
+pub fn start_timing(
+    clock: Res<RaceClock>,
+    mut timings: ResMut<RaceTimings>,
+    karts: Query<Entity, With<LapProgress>>,
+) {
+    timings.karts = karts
+        .iter()
+        .map(|kart| {
+            let timing = KartTiming {
+                lap_starts: vec![clock.elapsed],
+                ..default()
+            };
+            (kart, timing)
+        })
+        .collect();
+}
+
+fn delta(time: Duration, best: Duration) -> f32 {
+    time.as_secs_f32() - best.as_secs_f32()
+}
+
+/// Records the times of the karts from the lap events
+///
+/// A lap or checkpoint crossed again after going back through it replaces its
+/// previous time
+#[allow(clippy::too_many_arguments)]
+pub fn update_timing(
+    clock: Res<RaceClock>,
+    track: Res<Track>,
+    leaderboard: Res<Leaderboard>,
+    mut timings: ResMut<RaceTimings>,
+    mut lap_started: EventReader<LapStarted>,
+    mut checkpoint_passed: EventReader<CheckpointPassed>,
+    mut lap_completed: EventReader<LapCompleted>,
+    mut race_finished: EventReader<RaceFinished>,
+    players: Query<&KartId, With<Player>>,
+) {
+    let now = clock.elapsed;
+    // Only the player has records, the other karts have no delta
+    let personal_best = |kart: Entity| {
+        let kart_id = players.get(kart).ok()?;
+        leaderboard.record(&track.id, kart_id)
+    };
+
+    // A kart completes a lap and starts the next one on the same crossing, so
+    // the completed lap is recorded first
+    for LapCompleted { kart, lap } in lap_completed.iter() {
+        let Some(timing) = timings.karts.get_mut(kart) else {
+            continue;
+        };
+        let Some(&lap_start) = timing.lap_starts.get(*lap as usize - 1) else {
+            continue;
+        };
+
+        let lap_time = now.saturating_sub(lap_start);
+        timing.delta = personal_best(*kart)
+            .and_then(|record| record.best_lap)
+            .map(|best| delta(lap_time, best));
+        timing.laps.truncate(*lap as usize - 1);
+        timing.laps.push(lap_time);
+
+        let is_best = match timing.best_lap {
+            Some(best_lap) => lap_time < best_lap,
+            None => true,
+        };
+        if is_best {
+            timing.best_lap = Some(lap_time);
+            timing.best_splits = timing.splits.clone();
+        }
+
+        tracing::info!("Kart {kart:?} lap {lap}: {}", format_race_time(lap_time));
+    }
+
+    for LapStarted { kart, lap } in lap_started.iter() {
+        // The first lap started with the race, see `start_timing`
+        if *lap == 1 {
+            continue;
+        }
+        let Some(timing) = timings.karts.get_mut(kart) else {
+            continue;
+        };
+
+        timing.lap_starts.truncate(*lap as usize - 1);
+        timing.lap_starts.push(now);
+        timing.splits.clear();
+    }
+
+    for CheckpointPassed { kart, index } in checkpoint_passed.iter() {
+        let Some(timing) = timings.karts.get_mut(kart) else {
+            continue;
+        };
+        let Some(split) = timing.current_lap(&clock) else {
+            continue;
+        };
+
+        let best_split = personal_best(*kart)
+            .and_then(|record| record.best_splits.get(*index as usize).copied());
+        timing.delta = best_split.map(|best| delta(split, best));
+        timing.splits.truncate(*index as usize);
+        timing.splits.push(split);
+    }
+
+    for RaceFinished { kart } in race_finished.iter() {
+        let Some(timing) = timings.karts.get_mut(kart) else {
+            continue;
+        };
+
+        timing.total = Some(now);
+    }
+}
@@ -0,0 +1,131 @@
+use bevy::prelude::*;
+
+use super::state::{screen_node, text_style};
+use crate::{
+    kart::{Player, Speed},
+    track::racing_line::RacingLine,
+};
+
+/// Angle between the kart and the track's direction over which the kart goes
+/// the wrong way
+const WRONG_WAY_ANGLE: f32 = 120f32;
+/// Seconds the kart must drive the wrong way before it is told so
+const WRONG_WAY_SECONDS: f32 = 1f32;
+/// Speed under which the kart isn't driving, in meters per second
+const MIN_SPEED: f32 = 1f32;
+
+/// Whether the kart goes against the track's direction, given by its
+/// [`RacingLine`]
+#[derive(Debug, Component, Default)]
+pub struct WrongWay {
+    /// Set once the kart drove the wrong way for [`WRONG_WAY_SECONDS`], until
+    /// it faces the track's direction again
+    pub is_wrong_way: bool,
+    /// Seconds the kart has been driving the wrong way
+    time: f32,
+}
+
+/// Sent when a kart starts or stops going the wrong way
+#[derive(Debug, Event)]
+pub struct WrongWayChanged {
+    pub kart: Entity,
+    pub is_wrong_way: bool,
+}
+
+/// Root of the wrong way warning's UI
+#[derive(Debug, Component)]
+pub(super) struct WrongWayScreen;
+
+#[derive(Debug, Component)]
+pub(super) struct WrongWayText;
+
+/// Tells which karts drive forward against the track's direction
+///
+/// A kart needs to face the track's direction again to be back on the right
+/// way, so reversing to turn around doesn't clear it
+pub(super) fn detect_wrong_way(
+    time: Res<Time>,
+    racing_line: Res<RacingLine>,
+    mut karts: Query<(Entity, &Transform, &Speed, &mut WrongWay)>,
+    mut wrong_way_changed: EventWriter<WrongWayChanged>,
+) {
+    if !racing_line.is_valid() {
+        return;
+    }
+
+    let threshold = WRONG_WAY_ANGLE.to_radians().cos();
+    for (kart, transform, speed, mut wrong_way) in karts.iter_mut() {
+        let tangent = racing_line.closest(transform.translation).tangent;
+        // Front is +Z, `back` in Bevy's terms
+        let alignment = transform.back().dot(tangent);
+
+        if wrong_way.is_wrong_way {
+            if alignment > 0f32 {
+                *wrong_way = WrongWay::default();
+                wrong_way_changed.send(WrongWayChanged {
+                    kart,
+                    is_wrong_way: false,
+                });
+            }
+            continue;
+        }
+
+        if alignment < threshold && speed.forward_speed > MIN_SPEED {
+            wrong_way.time += time.delta_seconds();
+        } else {
+            wrong_way.time = 0f32;
+        }
+
+        if wrong_way.time > WRONG_WAY_SECONDS {
+            wrong_way.is_wrong_way = true;
+            wrong_way_changed.send(WrongWayChanged {
+                kart,
+                is_wrong_way: true,
+            });
+        }
+    }
+}
+
+pub(super) fn show_wrong_way(mut commands: Commands) {
+    commands
+        .spawn((screen_node(), WrongWayScreen))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle {
+                    visibility: Visibility::Hidden,
+                    ..TextBundle::from_section(
+                        "WRONG WAY",
+                        TextStyle {
+                            color: Color::RED,
+                            ..text_style(80f32)
+                        },
+                    )
+                },
+                WrongWayText,
+            ));
+        });
+}
+
+/// Shows the warning while the player goes the wrong way
+pub(super) fn update_wrong_way_warning(
+    players: Query<&WrongWay, With<Player>>,
+    mut query: Query<&mut Visibility, With<WrongWayText>>,
+) {
+    let is_wrong_way = players
+        .get_single()
+        .is_ok_and(|wrong_way| wrong_way.is_wrong_way);
+
+    for mut visibility in query.iter_mut() {
+        *visibility = if is_wrong_way {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+pub(super) fn hide_wrong_way(mut commands: Commands, query: Query<Entity, With<WrongWayScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
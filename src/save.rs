@@ -0,0 +1,140 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Directory of the saves, relative to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_DIR: &str = "saves";
+/// Prefix of the saves' keys in the browser's local storage
+#[cfg(target_arch = "wasm32")]
+const KEY_PREFIX: &str = "kart.";
+
+/// A save, a RON struct with a `version` field
+pub trait SaveFormat: Serialize + DeserializeOwned {
+    /// Bumped when the format changes, older saves are then read by
+    /// [`SaveFormat::migrate`]
+    const VERSION: u32;
+
+    /// Reads a save of an older `version`, given as a RON value
+    ///
+    /// There are no older versions by default, such saves are set aside
+    fn migrate(version: u32, _save: ron::Value) -> Result<Self, String> {
+        Err(format!("no migration from version {version}"))
+    }
+}
+
+/// Only the version of a save, read first so a save of another version is not
+/// mistaken for a corrupted one
+#[derive(Debug, serde::Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+fn parse<T: SaveFormat>(content: &str) -> Result<T, String> {
+    let header: SaveHeader = ron::from_str(content).map_err(|err| err.to_string())?;
+
+    if header.version > T::VERSION {
+        Err(format!(
+            "the save is version {}, newer than {}",
+            header.version,
+            T::VERSION
+        ))
+    } else if header.version < T::VERSION {
+        let save = ron::from_str(content).map_err(|err| err.to_string())?;
+        T::migrate(header.version, save)
+    } else {
+        ron::from_str(content).map_err(|err| err.to_string())
+    }
+}
+
+/// Reads the save `name`, migrating it if it is of an older version
+///
+/// Gives `None` when there is no save, or when it can't be read or is of a
+/// newer version. It is then copied aside as `<name>.bak`, so it is not lost
+/// once the save is written again
+pub fn load<T: SaveFormat>(name: &str) -> Option<T> {
+    let content = read(name)?;
+
+    match parse(&content) {
+        Ok(save) => Some(save),
+        Err(err) => {
+            tracing::warn!("Ignoring the save {name:?}, it is backed up: {err}");
+            if let Err(err) = write(&format!("{name}.bak"), &content) {
+                tracing::error!("Failed to back up the save {name:?}: {err}");
+            }
+            None
+        }
+    }
+}
+
+/// Writes the save `name`, see [`load`]
+///
+/// Errors are logged, the game goes on without saving
+pub fn store<T: SaveFormat>(name: &str, save: &T) {
+    let result = ron::to_string(save)
+        .map_err(|err| err.to_string())
+        .and_then(|content| write(name, &content));
+
+    if let Err(err) = result {
+        tracing::error!("Failed to write the save {name:?}: {err}");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_path(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(SAVE_DIR).join(format!("{name}.ron"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(name: &str) -> Option<String> {
+    let path = save_path(name);
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => {
+            tracing::warn!("Failed to read the save {path:?}: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(name: &str, content: &str) -> Result<(), String> {
+    let path = save_path(name);
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+    }
+
+    // Written aside then moved, so a crash never leaves half a file
+    let temporary_path = path.with_extension("tmp");
+    std::fs::write(&temporary_path, content).map_err(|err| err.to_string())?;
+    std::fs::rename(&temporary_path, &path).map_err(|err| err.to_string())
+}
+
+// There is no file system on the web, the saves go to the local storage
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, String> {
+    web_sys::window()
+        .ok_or("no window")?
+        .local_storage()
+        .map_err(|err| format!("{err:?}"))?
+        .ok_or_else(|| "no local storage".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(name: &str) -> Option<String> {
+    let storage = local_storage()
+        .map_err(|err| tracing::warn!("Failed to read the save {name:?}: {err}"))
+        .ok()?;
+
+    storage
+        .get_item(&format!("{KEY_PREFIX}{name}"))
+        .ok()
+        .flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(name: &str, content: &str) -> Result<(), String> {
+    local_storage()?
+        .set_item(&format!("{KEY_PREFIX}{name}"), content)
+        .map_err(|err| format!("{err:?}"))
+}
@@ -0,0 +1,56 @@
+use bevy::{
+    prelude::*,
+    scene::{scene_spawner_system, InstanceId, SceneInstance},
+};
+
+/// Sent once the scene of a [`SceneBundle`] is spawned, and again every time
+/// the entity's scene is changed and respawned
+///
+/// The scene's entities can be iterated with
+/// [`SceneSpawner::iter_instance_entities`]
+#[derive(Debug, Event)]
+pub struct SceneReady {
+    /// The entity with the [`SceneBundle`]
+    pub root: Entity,
+    pub instance: InstanceId,
+}
+
+/// Last instance of the entity's scene reported by a [`SceneReady`]
+#[derive(Debug, Component)]
+struct ReadySceneInstance(InstanceId);
+
+fn send_scene_ready(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    scenes: Query<(Entity, &SceneInstance, Option<&ReadySceneInstance>)>,
+    mut scene_ready: EventWriter<SceneReady>,
+) {
+    for (root, instance, ready_instance) in scenes.iter() {
+        let instance = **instance;
+        let is_reported = ready_instance.is_some_and(|ready| ready.0 == instance);
+
+        if is_reported || !scene_spawner.instance_is_ready(instance) {
+            continue;
+        }
+
+        commands.entity(root).insert(ReadySceneInstance(instance));
+        scene_ready.send(SceneReady { root, instance });
+    }
+}
+
+/// Sends [`SceneReady`] events, systems reading them should run in
+/// [`SceneReadySet`] to get them on the frame the scene is spawned
+pub struct SceneReadyPlugin;
+
+/// Systems reacting to [`SceneReady`] events
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemSet)]
+pub struct SceneReadySet;
+
+impl Plugin for SceneReadyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SceneReady>();
+
+        app.add_systems(Update, send_scene_ready.after(scene_spawner_system));
+        app.configure_set(Update, SceneReadySet.after(send_scene_ready));
+    }
+}
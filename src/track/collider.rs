@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    physics::{
+        collider::{mesh_triangles, transform_triangles},
+        transform_relative_to, MeshCollider,
+    },
+    scene::SceneReady,
+};
+
+/// Marks a track whose trimesh collider is built once its scene is spawned,
+/// see [`super::TrackCollision::TriMesh`]
+#[derive(Debug, Component)]
+pub struct PendingTrackCollider;
+
+/// Builds a single static trimesh collider from every mesh of the track's scene
+///
+/// The meshes of the [`MeshCollider`] nodes are left out, they get their own
+/// collider from [`crate::physics::GltfColliderPlugin`]
+///
+/// The vertices are moved in the space of the track's entity, so the meshes'
+/// translation, rotation and scale (even non-uniform) are honoured
+#[allow(clippy::too_many_arguments)]
+pub fn build_track_collider(
+    mut commands: Commands,
+    mut scene_ready: EventReader<SceneReady>,
+    scene_spawner: Res<SceneSpawner>,
+    meshes: Res<Assets<Mesh>>,
+    tracks: Query<(), With<PendingTrackCollider>>,
+    mesh_handles: Query<&Handle<Mesh>>,
+    mesh_colliders: Query<(), With<MeshCollider>>,
+    names: Query<&Name>,
+    transforms: Query<&Transform>,
+    parents: Query<&Parent>,
+) {
+    for SceneReady {
+        root: track,
+        instance,
+    } in scene_ready.iter()
+    {
+        if !tracks.contains(*track) {
+            continue;
+        }
+
+        let mut vertices: Vec<Vec3> = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        for entity in scene_spawner.iter_instance_entities(*instance) {
+            let Ok(mesh_handle) = mesh_handles.get(entity) else {
+                continue;
+            };
+            let has_own_collider = mesh_colliders.contains(entity)
+                || parents
+                    .get(entity)
+                    .is_ok_and(|parent| mesh_colliders.contains(parent.get()));
+            if has_own_collider {
+                continue;
+            }
+
+            let triangles = meshes
+                .get(mesh_handle)
+                .ok_or_else(|| "mesh not loaded".to_string())
+                .and_then(|mesh| mesh_triangles(mesh).map_err(|err| err.to_string()));
+            let (mut mesh_vertices, mut mesh_indices) = match triangles {
+                Ok(triangles) => triangles,
+                Err(err) => {
+                    let name = names.get(entity).map_or("unnamed", |name| name.as_str());
+                    tracing::warn!("Skipping mesh {name:?} of the track collider: {err}");
+                    continue;
+                }
+            };
+
+            let transform = transform_relative_to(entity, *track, &transforms, &parents);
+            transform_triangles(transform, &mut mesh_vertices, &mut mesh_indices);
+
+            let offset = vertices.len() as u32;
+            vertices.extend(mesh_vertices);
+            indices.extend(
+                mesh_indices
+                    .iter()
+                    .map(|[a, b, c]| [a + offset, b + offset, c + offset]),
+            );
+        }
+
+        let mut track_commands = commands.entity(*track);
+        track_commands.remove::<PendingTrackCollider>();
+        if indices.is_empty() {
+            tracing::error!("The track's scene has no mesh, it has no collider");
+            continue;
+        }
+
+        tracing::info!("Built the track collider with {} triangles", indices.len());
+        track_commands.insert(Collider::trimesh(vertices, indices));
+    }
+}
@@ -0,0 +1,129 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+pub mod collider;
+pub mod racing_line;
+pub mod registry;
+pub mod spawn;
+
+/// Marks the entity with the track's scene
+#[derive(Debug, Component)]
+pub struct TrackScene;
+
+/// Where the karts are placed at the start of a race, as written in the track
+/// manifest, for the tracks without [`spawn::SpawnPoint`]s
+///
+/// Slots are filled row by row, the first slot being the pole position
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnGrid {
+    /// Position of the pole position
+    pub origin: [f32; 3],
+    /// Direction the karts face in degrees, around the up axis, 0 faces +Z
+    pub facing: f32,
+    /// Number of karts per row
+    pub columns: usize,
+    /// Distance between two rows in meters
+    pub row_spacing: f32,
+    /// Distance between two karts of the same row in meters
+    pub column_spacing: f32,
+}
+
+impl SpawnGrid {
+    /// Transform of the kart in the `index`-th slot of the grid
+    pub fn slot(&self, index: usize) -> Transform {
+        let row = (index / self.columns) as f32;
+        let column = (index % self.columns) as f32;
+
+        // Rows go backward from the pole position, columns go to the right
+        // (-X in the karts' space, front is +Z)
+        let rotation = Quat::from_rotation_y(self.facing.to_radians());
+        let offset = Vec3::new(-column * self.column_spacing, 0f32, -row * self.row_spacing);
+
+        Transform::from_translation(Vec3::from(self.origin) + rotation * offset)
+            .with_rotation(rotation)
+    }
+}
+
+/// Shape of a track's collider, as written in the track manifest
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum TrackCollision {
+    /// A box, centered on `translation`
+    Cuboid {
+        half_extents: [f32; 3],
+        translation: [f32; 3],
+    },
+    /// Every mesh of the track's scene, as a static trimesh
+    ///
+    /// Built once the scene is spawned, see [`collider::build_track_collider`]
+    TriMesh,
+}
+
+impl TrackCollision {
+    /// Gives its collider to the track's `RigidBody` entity
+    pub fn insert(&self, track: &mut EntityCommands) {
+        match self {
+            TrackCollision::Cuboid {
+                half_extents,
+                translation,
+            } => {
+                track.with_children(|parent| {
+                    parent.spawn((
+                        Collider::cuboid(half_extents[0], half_extents[1], half_extents[2]),
+                        TransformBundle::from_transform(Transform::from_translation(Vec3::from(
+                            *translation,
+                        ))),
+                    ));
+                });
+            }
+            TrackCollision::TriMesh => {
+                track.insert(collider::PendingTrackCollider);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(facing: f32) -> SpawnGrid {
+        SpawnGrid {
+            origin: [10f32, 1f32, 20f32],
+            facing,
+            columns: 3,
+            row_spacing: 4f32,
+            column_spacing: 2f32,
+        }
+    }
+
+    #[test]
+    fn pole_position_is_the_origin() {
+        let slot = grid(90f32).slot(0);
+
+        assert_eq!(slot.translation, Vec3::new(10f32, 1f32, 20f32));
+        assert!((slot.rotation * Vec3::Z - Vec3::X).length() < 1e-5);
+    }
+
+    #[test]
+    fn slots_wrap_to_the_next_row() {
+        let grid = grid(0f32);
+
+        // Columns go to the right of the karts (-X), rows go backward (-Z)
+        assert_eq!(grid.slot(1).translation, Vec3::new(8f32, 1f32, 20f32));
+        assert_eq!(grid.slot(2).translation, Vec3::new(6f32, 1f32, 20f32));
+        assert_eq!(grid.slot(3).translation, Vec3::new(10f32, 1f32, 16f32));
+        assert_eq!(grid.slot(7).translation, Vec3::new(8f32, 1f32, 12f32));
+    }
+
+    #[test]
+    fn grid_turns_with_its_facing() {
+        let grid = grid(90f32);
+
+        // Facing +X, the right of the karts is +Z and backward is -X
+        let slot = grid.slot(4);
+        assert!((slot.translation - Vec3::new(6f32, 1f32, 22f32)).length() < 1e-5);
+        assert!((slot.rotation * Vec3::Z - Vec3::X).length() < 1e-5);
+    }
+}
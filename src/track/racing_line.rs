@@ -0,0 +1,308 @@
+use bevy::{math::Affine3A, prelude::*};
+
+use crate::{physics::transform_relative_to, scene::SceneReady, track::TrackScene};
+
+/// Points sampled on the spline between two waypoints
+const SAMPLES_PER_SEGMENT: usize = 8;
+
+/// A point of the track's centerline, the line loops from the last waypoint
+/// back to the first through a Catmull-Rom spline
+///
+/// Set from the node's custom properties, e.g. `Waypoint: (index: 0)`. The
+/// node's X scale is the half width of the track, like the cube of a
+/// [`crate::race::checkpoint::Checkpoint`]
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Waypoint {
+    pub index: u32,
+}
+
+/// A point of the [`RacingLine`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineSample {
+    /// Distance in meters from the first waypoint, along the line
+    pub distance: f32,
+    pub point: Vec3,
+    /// Direction of the line, normalized
+    pub tangent: Vec3,
+    /// Width of the track in meters
+    pub width: f32,
+}
+
+/// The centerline of the track, from the [`Waypoint`]s of the track's scene
+///
+/// Positions along the line are given as a distance in meters from the first
+/// waypoint, wrapping around the loop
+#[derive(Debug, Resource, Default)]
+pub struct RacingLine {
+    /// Points sampled on the spline, in racing order
+    points: Vec<Vec3>,
+    /// Half width of the track at each point
+    half_widths: Vec<f32>,
+    /// Distance of each point from the first one, then the length of the loop
+    distances: Vec<f32>,
+}
+
+/// Point at `t`, from 0 to 1, of the uniform Catmull-Rom segment from `p1`
+/// to `p2`
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5f32
+        * (2f32 * p1
+            + (p2 - p0) * t
+            + (2f32 * p0 - 5f32 * p1 + 4f32 * p2 - p3) * t2
+            + (3f32 * p1 - p0 - 3f32 * p2 + p3) * t3)
+}
+
+impl RacingLine {
+    /// Samples the closed spline through the `waypoints`, given with their
+    /// half width
+    pub(crate) fn from_waypoints(waypoints: &[(Vec3, f32)]) -> Self {
+        let count = waypoints.len();
+        let mut points = Vec::with_capacity(count * SAMPLES_PER_SEGMENT);
+        let mut half_widths = Vec::with_capacity(count * SAMPLES_PER_SEGMENT);
+
+        for index in 0..count {
+            let (p0, _) = waypoints[(index + count - 1) % count];
+            let (p1, w1) = waypoints[index];
+            let (p2, w2) = waypoints[(index + 1) % count];
+            let (p3, _) = waypoints[(index + 2) % count];
+
+            for sample in 0..SAMPLES_PER_SEGMENT {
+                let t = sample as f32 / SAMPLES_PER_SEGMENT as f32;
+                points.push(catmull_rom(p0, p1, p2, p3, t));
+                half_widths.push(w1 + (w2 - w1) * t);
+            }
+        }
+
+        let mut distances = Vec::with_capacity(points.len() + 1);
+        let mut distance = 0f32;
+        for (index, point) in points.iter().enumerate() {
+            distances.push(distance);
+            distance += point.distance(points[(index + 1) % points.len()]);
+        }
+        distances.push(distance);
+
+        Self {
+            points,
+            half_widths,
+            distances,
+        }
+    }
+
+    /// Whether the track has a racing line, it needs at least two waypoints
+    pub fn is_valid(&self) -> bool {
+        self.points.len() >= 2 && self.length() > 0f32
+    }
+
+    /// Length of the whole loop in meters
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0f32)
+    }
+
+    /// The points sampled along the line, in racing order
+    #[cfg(feature = "debug_racing_line")]
+    pub fn samples(&self) -> impl Iterator<Item = LineSample> + '_ {
+        (0..self.points.len()).map(|index| self.sample_segment(index, 0f32))
+    }
+
+    /// Start and end of the `index`-th segment between two sampled points
+    fn segment(&self, index: usize) -> (Vec3, Vec3) {
+        (
+            self.points[index],
+            self.points[(index + 1) % self.points.len()],
+        )
+    }
+
+    /// The line at `along`, from 0 to 1, on the `index`-th segment
+    fn sample_segment(&self, index: usize, along: f32) -> LineSample {
+        let (start, end) = self.segment(index);
+        let next_index = (index + 1) % self.points.len();
+        let half_width = self.half_widths[index]
+            + (self.half_widths[next_index] - self.half_widths[index]) * along;
+
+        LineSample {
+            distance: self.distances[index]
+                + (self.distances[index + 1] - self.distances[index]) * along,
+            point: start.lerp(end, along),
+            tangent: (end - start).normalize_or_zero(),
+            width: 2f32 * half_width,
+        }
+    }
+
+    /// The line at `distance` along it, wrapping around the loop
+    ///
+    /// Must only be called on a valid line, see [`RacingLine::is_valid`]
+    pub fn sample(&self, distance: f32) -> LineSample {
+        let distance = distance.rem_euclid(self.length());
+        let index = self
+            .distances
+            .partition_point(|start| *start <= distance)
+            .saturating_sub(1)
+            .min(self.points.len() - 1);
+
+        let segment_length = self.distances[index + 1] - self.distances[index];
+        let along = (distance - self.distances[index]) / segment_length.max(f32::EPSILON);
+        self.sample_segment(index, along)
+    }
+
+    /// The point of the line closest to `position`
+    ///
+    /// Must only be called on a valid line, see [`RacingLine::is_valid`]
+    pub fn closest(&self, position: Vec3) -> LineSample {
+        let mut closest = (f32::INFINITY, 0, 0f32);
+
+        for index in 0..self.points.len() {
+            let (start, end) = self.segment(index);
+            let segment = end - start;
+            let along = ((position - start).dot(segment)
+                / segment.length_squared().max(f32::EPSILON))
+            .clamp(0f32, 1f32);
+
+            let distance_squared = position.distance_squared(start + segment * along);
+            if distance_squared < closest.0 {
+                closest = (distance_squared, index, along);
+            }
+        }
+
+        self.sample_segment(closest.1, closest.2)
+    }
+}
+
+/// Reads the [`Waypoint`]s of the track once its scene is spawned
+pub fn collect_waypoints(
+    mut scene_ready: EventReader<SceneReady>,
+    scene_spawner: Res<SceneSpawner>,
+    mut racing_line: ResMut<RacingLine>,
+    track_scenes: Query<(), With<TrackScene>>,
+    waypoints: Query<&Waypoint>,
+    transform_query: Query<&Transform>,
+    parent_query: Query<&Parent>,
+) {
+    for SceneReady { root, instance } in scene_ready.iter() {
+        if !track_scenes.contains(*root) {
+            continue;
+        }
+
+        let root_transform = transform_query
+            .get(*root)
+            .map_or(Affine3A::IDENTITY, Transform::compute_affine);
+
+        let mut points: Vec<(u32, (Vec3, f32))> = scene_spawner
+            .iter_instance_entities(*instance)
+            .filter_map(|entity| {
+                let waypoint = waypoints.get(entity).ok()?;
+                let affine = root_transform
+                    * transform_relative_to(entity, *root, &transform_query, &parent_query);
+                let (scale, _, translation) = affine.to_scale_rotation_translation();
+
+                Some((waypoint.index, (translation, scale.x)))
+            })
+            .collect();
+        points.sort_by_key(|(index, _)| *index);
+
+        let points: Vec<(Vec3, f32)> = points.into_iter().map(|(_, point)| point).collect();
+        *racing_line = RacingLine::from_waypoints(&points);
+        if racing_line.is_valid() {
+            tracing::info!(
+                "Found a racing line of {:.0}m in the track",
+                racing_line.length()
+            );
+        } else {
+            tracing::warn!("The track has no racing line, it needs at least two waypoints");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10m wide square loop, narrowing to 4m on its last side
+    fn square() -> RacingLine {
+        RacingLine::from_waypoints(&[
+            (Vec3::new(0f32, 0f32, 0f32), 5f32),
+            (Vec3::new(10f32, 0f32, 0f32), 5f32),
+            (Vec3::new(10f32, 0f32, 10f32), 5f32),
+            (Vec3::new(0f32, 0f32, 10f32), 2f32),
+        ])
+    }
+
+    #[test]
+    fn line_needs_two_waypoints() {
+        assert!(!RacingLine::default().is_valid());
+        assert!(!RacingLine::from_waypoints(&[(Vec3::ZERO, 1f32)]).is_valid());
+        assert!(!RacingLine::from_waypoints(&[(Vec3::ZERO, 1f32), (Vec3::ZERO, 1f32)]).is_valid());
+        assert!(RacingLine::from_waypoints(&[(Vec3::ZERO, 1f32), (Vec3::X, 1f32)]).is_valid());
+    }
+
+    #[test]
+    fn sample_wraps_around_the_loop() {
+        let line = square();
+        let length = line.length();
+
+        for distance in [0f32, 3.5, 17f32, length - 0.5] {
+            let sample = line.sample(distance);
+            for wrapped in [
+                distance - length,
+                distance + length,
+                distance + 3f32 * length,
+            ] {
+                let wrapped = line.sample(wrapped);
+                assert!(
+                    wrapped.point.abs_diff_eq(sample.point, 1e-3),
+                    "{wrapped:?} != {sample:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sample_goes_through_the_waypoints() {
+        let line = square();
+
+        let start = line.sample(0f32);
+        assert_eq!(start.point, Vec3::ZERO);
+        assert_eq!(start.width, 10f32);
+
+        let last = line.closest(Vec3::new(0f32, 0f32, 10f32));
+        assert!(last.point.abs_diff_eq(Vec3::new(0f32, 0f32, 10f32), 1e-5));
+        assert!((last.width - 4f32).abs() < 1e-5);
+        assert!(line
+            .sample(last.distance)
+            .point
+            .abs_diff_eq(last.point, 1e-3));
+
+        // The loop goes from the last waypoint back to the first
+        let closing = line.sample(line.length() - 0.1);
+        assert!(closing.tangent.z < 0f32);
+    }
+
+    #[test]
+    fn closest_point_is_on_the_line() {
+        // Along X then back
+        let line = RacingLine::from_waypoints(&[(Vec3::ZERO, 1f32), (Vec3::X * 10f32, 1f32)]);
+        assert!((line.length() - 20f32).abs() < 1e-4);
+
+        let closest = line.closest(Vec3::new(4f32, 3f32, 1f32));
+        assert!(closest.point.abs_diff_eq(Vec3::X * 4f32, 1e-4));
+        assert!(closest.tangent.abs_diff_eq(Vec3::X, 1e-4));
+        assert!((closest.distance - 4f32).abs() < 1e-4);
+
+        // Past the end of the line
+        let closest = line.closest(Vec3::new(15f32, 0f32, 0f32));
+        assert!(closest.point.abs_diff_eq(Vec3::X * 10f32, 1e-4));
+    }
+
+    #[test]
+    fn closest_point_of_a_corner() {
+        let line = square();
+
+        // Above the first waypoint, on the distance 0 and not the end of the loop
+        let closest = line.closest(Vec3::new(0f32, 5f32, 0f32));
+        assert_eq!(closest.point, Vec3::ZERO);
+        assert_eq!(closest.distance, 0f32);
+    }
+}
@@ -0,0 +1,277 @@
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::HashSet,
+};
+use bevy_asset_loader::prelude::DynamicAssets;
+
+use super::{SpawnGrid, TrackCollision};
+use crate::assets::{AssetLoadingState, DynamicHandles, ManifestAssets, RonAsset, RonLoader};
+
+/// Identifier of a track, as written in the track manifest
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Deserialize,
+    serde::Serialize
+)]
+#[serde(transparent)]
+pub struct TrackId(pub String);
+
+/// A track of the track manifest
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackManifestEntry {
+    pub id: TrackId,
+    /// Name shown to the players
+    pub name: String,
+    /// Path to the track's glTF scene, e.g. `terrains/map01.glb#Scene0`
+    pub scene: String,
+    pub collision: TrackCollision,
+    pub spawn_grid: SpawnGrid,
+    /// Number of laps of a race
+    pub laps: u32,
+    /// Height under which a kart is out of the track and respawns
+    #[serde(default = "default_kill_y")]
+    pub kill_y: f32,
+    /// Path to the music played in loop during the race
+    #[serde(default)]
+    pub music: Option<String>,
+    /// Path to a cubemap image (e.g. a `.ktx2`) used as the skybox
+    #[serde(default)]
+    pub skybox: Option<String>,
+}
+
+fn default_kill_y() -> f32 {
+    -20f32
+}
+
+/// Every track of the game, loaded from a `.tracks.ron` file
+#[derive(Debug, serde::Deserialize, TypeUuid, TypePath)]
+#[uuid = "3f9d5a61-2c8e-4b07-a1f4-8d6e0b93c25f"]
+#[serde(deny_unknown_fields)]
+pub struct TrackManifest {
+    pub tracks: Vec<TrackManifestEntry>,
+}
+
+impl RonAsset for TrackManifest {
+    type Error = String;
+
+    const NAME: &'static str = "track manifest";
+    const EXTENSIONS: &'static [&'static str] = &["tracks.ron"];
+
+    fn validate(&self) -> Result<(), String> {
+        if self.tracks.is_empty() {
+            return Err("the manifest has no track".to_string());
+        }
+
+        let mut ids = HashSet::new();
+        for track in self.tracks.iter() {
+            if !ids.insert(&track.id) {
+                return Err(format!("track {:?} is declared more than once", track.id.0));
+            }
+            if track.laps == 0 {
+                return Err(format!("track {:?} has no lap", track.id.0));
+            }
+            if track.spawn_grid.columns == 0 {
+                return Err(format!("track {:?} has an empty spawn grid", track.id.0));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl TrackManifest {
+    /// The track with the given `id`, or the first track of the manifest
+    fn get_or_default(&self, id: Option<&TrackId>) -> &TrackManifestEntry {
+        let track = id.and_then(|id| self.tracks.iter().find(|track| track.id == *id));
+
+        if let (Some(id), None) = (id, track) {
+            let ids: Vec<&str> = self
+                .tracks
+                .iter()
+                .map(|track| track.id.0.as_str())
+                .collect();
+            tracing::warn!(
+                "Unknown track {:?}, available tracks are {ids:?}, using the default one",
+                id.0
+            );
+        }
+
+        // The manifest is validated to have at least one track
+        track.unwrap_or(&self.tracks[0])
+    }
+}
+
+/// Track chosen at startup, the first track of the manifest when `None`
+#[derive(Debug, Default, Resource)]
+pub struct SelectedTrack(pub Option<TrackId>);
+
+impl SelectedTrack {
+    /// Reads the track from the `--track <id>` command line argument
+    pub fn from_args() -> Self {
+        let mut args = std::env::args().skip_while(|arg| arg != "--track").skip(1);
+
+        Self(args.next().map(TrackId))
+    }
+}
+
+/// The track of the race, with its assets
+///
+/// Available once [`AssetLoadingState::AssetLoading`] is entered, the assets
+/// are loaded when reaching [`AssetLoadingState::Done`]
+#[derive(Debug, Resource)]
+pub struct Track {
+    pub id: TrackId,
+    pub name: String,
+    pub scene: Handle<Scene>,
+    pub collision: TrackCollision,
+    pub spawn_grid: SpawnGrid,
+    pub laps: u32,
+    pub kill_y: f32,
+    pub music: Option<Handle<AudioSource>>,
+    pub skybox: Option<Handle<Image>>,
+}
+
+/// Builds the [`Track`] of the [`SelectedTrack`] from the loaded manifest and
+/// registers its assets to be loaded in [`AssetLoadingState::AssetLoading`]
+///
+/// Only the selected track is loaded
+fn register_track(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    selected_track: Res<SelectedTrack>,
+    manifest_assets: Res<ManifestAssets>,
+    manifests: Res<Assets<TrackManifest>>,
+    mut dynamic_assets: ResMut<DynamicAssets>,
+) {
+    let manifest = manifests
+        .get(&manifest_assets.tracks)
+        .expect("Track manifest is loaded with the manifest assets");
+    let entry = manifest.get_or_default(selected_track.0.as_ref());
+
+    let track = Track {
+        id: entry.id.clone(),
+        name: entry.name.clone(),
+        scene: asset_server.load(&entry.scene),
+        collision: entry.collision.clone(),
+        spawn_grid: entry.spawn_grid.clone(),
+        laps: entry.laps,
+        kill_y: entry.kill_y,
+        music: entry.music.as_ref().map(|music| asset_server.load(music)),
+        skybox: entry
+            .skybox
+            .as_ref()
+            .map(|skybox| asset_server.load(skybox)),
+    };
+
+    let mut handles = vec![track.scene.clone_untyped()];
+    handles.extend(track.music.iter().map(|music| music.clone_untyped()));
+    handles.extend(track.skybox.iter().map(|skybox| skybox.clone_untyped()));
+    dynamic_assets.register_asset("track", Box::new(DynamicHandles(handles)));
+
+    tracing::info!("Selected track {:?}, {} laps", track.id.0, track.laps);
+    commands.insert_resource(track);
+}
+
+pub struct TrackRegistryPlugin;
+
+impl Plugin for TrackRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<TrackManifest>();
+        app.init_asset_loader::<RonLoader<TrackManifest>>();
+
+        app.init_resource::<SelectedTrack>();
+        app.add_systems(OnExit(AssetLoadingState::Manifests), register_track);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str) -> TrackManifestEntry {
+        TrackManifestEntry {
+            id: TrackId(id.to_string()),
+            name: id.to_string(),
+            scene: format!("terrains/{id}.glb#Scene0"),
+            collision: TrackCollision::Cuboid {
+                half_extents: [1f32; 3],
+                translation: [0f32; 3],
+            },
+            spawn_grid: SpawnGrid {
+                origin: [0f32; 3],
+                facing: 0f32,
+                columns: 2,
+                row_spacing: 4f32,
+                column_spacing: 3f32,
+            },
+            laps: 3,
+            kill_y: default_kill_y(),
+            music: None,
+            skybox: None,
+        }
+    }
+
+    #[test]
+    fn shipped_manifest_is_valid() {
+        let manifest: TrackManifest =
+            ron::from_str(include_str!("../../assets/terrains/manifest.tracks.ron")).unwrap();
+        assert_eq!(manifest.validate(), Ok(()));
+    }
+
+    #[test]
+    fn manifest_needs_a_track() {
+        let manifest = TrackManifest { tracks: vec![] };
+        assert!(manifest.validate().is_err());
+
+        let manifest = TrackManifest {
+            tracks: vec![track("map01")],
+        };
+        assert_eq!(manifest.validate(), Ok(()));
+    }
+
+    #[test]
+    fn track_ids_are_unique() {
+        let manifest = TrackManifest {
+            tracks: vec![track("map01"), track("map02"), track("map01")],
+        };
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn track_needs_laps_and_columns() {
+        let manifest = TrackManifest {
+            tracks: vec![TrackManifestEntry {
+                laps: 0,
+                ..track("map01")
+            }],
+        };
+        assert!(manifest.validate().is_err());
+
+        let mut entry = track("map01");
+        entry.spawn_grid.columns = 0;
+        let manifest = TrackManifest {
+            tracks: vec![entry],
+        };
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn unknown_track_falls_back_to_the_first() {
+        let manifest = TrackManifest {
+            tracks: vec![track("map01"), track("map02")],
+        };
+
+        let id = TrackId("map02".to_string());
+        assert_eq!(manifest.get_or_default(Some(&id)).id, id);
+
+        let id = TrackId("map03".to_string());
+        assert_eq!(manifest.get_or_default(Some(&id)).id.0, "map01");
+        assert_eq!(manifest.get_or_default(None).id.0, "map01");
+    }
+}
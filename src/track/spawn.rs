@@ -0,0 +1,238 @@
+use bevy::{math::Affine3A, prelude::*};
+use bevy_rapier3d::{
+    math::Real,
+    na::Isometry3,
+    parry::{query, shape::Cuboid},
+};
+
+use super::{SpawnGrid, TrackScene};
+use crate::{kart::CHASSIS_HALF_EXTENTS, physics::transform_relative_to, scene::SceneReady};
+
+/// Free space around each kart on the grid, in meters
+const SLOT_MARGIN: f32 = 0.25f32;
+
+/// A slot of the starting grid, the kart is placed on the node and faces its
+/// local +Z
+///
+/// Set from the node's custom properties, e.g. `SpawnPoint: (slot: 0)`, the
+/// slot 0 being the pole position
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct SpawnPoint {
+    pub slot: u32,
+}
+
+/// Where the karts start and respawn, from the [`SpawnPoint`]s of the track's
+/// scene
+#[derive(Debug, Resource, Default)]
+pub struct SpawnPoints {
+    /// In slot order, `None` until the track's scene is spawned
+    slots: Option<Vec<Transform>>,
+}
+
+impl SpawnPoints {
+    /// Whether the track's scene is spawned, so its spawn points are known
+    pub fn is_ready(&self) -> bool {
+        self.slots.is_some()
+    }
+
+    /// Transform of the kart in the `index`-th slot
+    ///
+    /// Tracks without enough [`SpawnPoint`]s use the `grid` of their manifest
+    pub fn slot(&self, index: usize, grid: &SpawnGrid) -> Transform {
+        self.slots
+            .as_ref()
+            .and_then(|slots| slots.get(index))
+            .copied()
+            .unwrap_or_else(|| grid.slot(index))
+    }
+
+    /// First two slots where karts with a chassis of `half_extents` would
+    /// overlap
+    pub fn overlapping_slots(&self, half_extents: Vec3) -> Option<(usize, usize)> {
+        let slots = self.slots.as_deref()?;
+        let shape = Cuboid::new(half_extents.into());
+        let isometry = |slot: &Transform| -> Isometry3<Real> {
+            Isometry3::from_parts(slot.translation.into(), slot.rotation.into())
+        };
+
+        for (index, slot) in slots.iter().enumerate() {
+            for (other_index, other) in slots.iter().enumerate().skip(index + 1) {
+                let overlaps =
+                    query::intersection_test(&isometry(slot), &shape, &isometry(other), &shape)
+                        .unwrap_or(false);
+                if overlaps {
+                    return Some((index, other_index));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Reads the [`SpawnPoint`]s of the track once its scene is spawned
+pub fn collect_spawn_points(
+    mut scene_ready: EventReader<SceneReady>,
+    scene_spawner: Res<SceneSpawner>,
+    mut spawn_points: ResMut<SpawnPoints>,
+    track_scenes: Query<(), With<TrackScene>>,
+    points: Query<&SpawnPoint>,
+    transform_query: Query<&Transform>,
+    parent_query: Query<&Parent>,
+) {
+    for SceneReady { root, instance } in scene_ready.iter() {
+        if !track_scenes.contains(*root) {
+            continue;
+        }
+
+        let mut slots: Vec<(u32, Transform)> = Vec::new();
+        for entity in scene_spawner.iter_instance_entities(*instance) {
+            let Ok(point) = points.get(entity) else {
+                continue;
+            };
+
+            let root_transform = transform_query
+                .get(*root)
+                .map_or(Affine3A::IDENTITY, Transform::compute_affine);
+            let affine = root_transform
+                * transform_relative_to(entity, *root, &transform_query, &parent_query);
+
+            // The node's scale would scale the kart
+            let (_, rotation, translation) = affine.to_scale_rotation_translation();
+            slots.push((
+                point.slot,
+                Transform::from_translation(translation).with_rotation(rotation),
+            ));
+        }
+
+        slots.sort_by_key(|(slot, _)| *slot);
+        for (index, (slot, _)) in slots.iter().enumerate() {
+            if *slot != index as u32 {
+                tracing::warn!("The track's spawn points skip or repeat slot {index}, got {slot}");
+                break;
+            }
+        }
+
+        tracing::info!("Found {} spawn points in the track", slots.len());
+        spawn_points.slots = Some(slots.into_iter().map(|(_, transform)| transform).collect());
+
+        if let Some((slot, other_slot)) =
+            spawn_points.overlapping_slots(CHASSIS_HALF_EXTENTS + SLOT_MARGIN)
+        {
+            tracing::warn!(
+                "The track's spawn points {slot} and {other_slot} are too close, the karts overlap"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transform and custom properties of the track's nodes, the scene's
+    /// nodes have no parent
+    fn track_nodes() -> Vec<(Transform, serde_json::Value)> {
+        let glb = include_bytes!("../../assets/terrains/map01.glb");
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let gltf: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+
+        let vec = |value: &serde_json::Value| -> Vec<f32> {
+            value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|value| value.as_f64().unwrap() as f32)
+                .collect()
+        };
+        gltf["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| {
+                let mut transform = Transform::default();
+                if !node["translation"].is_null() {
+                    transform.translation = Vec3::from_slice(&vec(&node["translation"]));
+                }
+                if !node["rotation"].is_null() {
+                    transform.rotation = Quat::from_slice(&vec(&node["rotation"]));
+                }
+                if !node["scale"].is_null() {
+                    transform.scale = Vec3::from_slice(&vec(&node["scale"]));
+                }
+                (transform, node["extras"].clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shipped_spawn_points_fit_the_karts() {
+        #[derive(serde::Deserialize)]
+        struct Slot {
+            slot: u32,
+        }
+
+        let nodes = track_nodes();
+        let mut slots: Vec<(u32, Transform)> = nodes
+            .iter()
+            .filter_map(|(transform, extras)| {
+                let slot: Slot = ron::from_str(extras["SpawnPoint"].as_str()?).unwrap();
+                Some((slot.slot, *transform))
+            })
+            .collect();
+        slots.sort_by_key(|(slot, _)| *slot);
+        let spawn_points = SpawnPoints {
+            slots: Some(slots.into_iter().map(|(_, transform)| transform).collect()),
+        };
+        assert_eq!(spawn_points.slots.as_ref().unwrap().len(), 4);
+        assert_eq!(
+            spawn_points.overlapping_slots(CHASSIS_HALF_EXTENTS + SLOT_MARGIN),
+            None
+        );
+
+        // The karts start behind the finish line, out of its volume
+        let (finish_line, _) = nodes
+            .iter()
+            .find(|(_, extras)| !extras["FinishLine"].is_null())
+            .unwrap();
+        let (_, chassis, _, chassis_transform) = crate::kart::chassis_collider();
+        let finish_shape = Cuboid::new(finish_line.scale.into());
+        let finish_isometry =
+            Isometry3::from_parts(finish_line.translation.into(), finish_line.rotation.into());
+        for slot in spawn_points.slots.as_ref().unwrap() {
+            let chassis_transform = *slot * chassis_transform.local;
+            let chassis_isometry = Isometry3::from_parts(
+                chassis_transform.translation.into(),
+                chassis_transform.rotation.into(),
+            );
+            let overlaps = query::intersection_test(
+                &chassis_isometry,
+                &*chassis.raw,
+                &finish_isometry,
+                &finish_shape,
+            )
+            .unwrap();
+
+            assert!(!overlaps, "{slot:?} overlaps the finish line");
+            let forward = finish_line.rotation * Vec3::Z;
+            assert!((slot.translation - finish_line.translation).dot(forward) < 0f32);
+        }
+    }
+
+    #[test]
+    fn close_slots_overlap() {
+        let slot = |x: f32| Transform::from_xyz(x, 0f32, 0f32);
+        let spawn_points = SpawnPoints {
+            slots: Some(vec![slot(0f32), slot(2.5f32), slot(4f32)]),
+        };
+
+        // Karts 2 wide, 4 long along Z, side by side
+        let half_extents = Vec3::new(1f32, 0.5f32, 2f32);
+        assert_eq!(spawn_points.overlapping_slots(half_extents), Some((1, 2)));
+        assert_eq!(
+            spawn_points.overlapping_slots(half_extents + Vec3::X * 0.6f32),
+            Some((0, 1))
+        );
+    }
+}